@@ -0,0 +1,64 @@
+use crate::commands::open_store;
+use password_store_rs::utils::yesno;
+use password_store_rs::StoreError;
+
+/// Moves or copies a password entry or directory, similar to pass's cmd_copy_move.
+///
+/// This function performs the following steps:
+///
+/// 1. Resolves the destination in the store. If `<store>/<new>` is an existing directory, or
+///    `new` ends with `/`, the source is placed inside it under its own name. Otherwise a file
+///    source becomes `<store>/<new>.gpg` and a directory source becomes `<store>/<new>`.
+/// 2. Asks for confirmation before replacing an existing destination unless `force` is set.
+/// 3. Renames or recursively copies the source to the destination, re-encrypting every entry
+///    whose governing `.gpg-id` differs between its old and its new location, so that entries
+///    moved into a folder initialized with `init --path` are readable by that folder's
+///    recipients (see [`password_store_rs::PasswordStore::rename`] and
+///    [`password_store_rs::PasswordStore::copy`]).
+/// 4. Records the whole operation as a single Git commit and, for moves, removes source
+///    directories left empty.
+///
+/// # Arguments
+///
+/// * `old` - The name of the entry or directory to move or copy.
+/// * `new` - The destination name.
+/// * `force` - If true, an existing destination is replaced without prompting.
+/// * `move_entry` - If true the source is moved, otherwise it is copied.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - Either name contains suspicious path segments.
+/// - The source does not exist.
+/// - The destination is the source itself or lies inside it.
+/// - Filesystem, GPG or Git operations fail.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Rename an entry.
+/// cmd_copy_move("example.com", "web/example.com", false, true)?;
+///
+/// // Copy a folder into a shared folder with its own recipients.
+/// cmd_copy_move("personal/bank", "shared/", false, false)?;
+/// ```
+pub fn cmd_copy_move(
+    old: &str,
+    new: &str,
+    force: bool,
+    move_entry: bool,
+) -> Result<(), StoreError> {
+    let store = open_store()?;
+
+    let exists = store.destination_exists(old, new)?;
+    if exists && !force && !yesno(&format!("{} already exists. Overwrite it?", new)) {
+        println!("Aborting.");
+        return Ok(());
+    }
+
+    if move_entry {
+        store.rename(old, new, true)
+    } else {
+        store.copy(old, new, true)
+    }
+}
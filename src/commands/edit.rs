@@ -0,0 +1,242 @@
+use crate::commands::add::ensure_recipient_keys;
+use crate::commands::open_store;
+use password_store_rs::utils::check_sneaky_paths;
+use password_store_rs::StoreError;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+
+/// Edits a password entry with the user's text editor, similar to pass's cmd_edit.
+///
+/// This function performs the following steps:
+/// 1. Validates `pass_name` with [`check_sneaky_paths`] and reads the store's recipients.
+/// 2. Decrypts the existing entry, if there is one. A missing entry starts out empty.
+/// 3. Writes the plaintext to a private temporary file (see [`SecureTempDir`]) and opens it
+///    with `$EDITOR`, falling back to `$VISUAL` and then `vi`.
+/// 4. Reads the result back and securely removes the temporary file before doing anything else.
+/// 5. If the contents changed, re-encrypts the entry and commits it to Git (see
+///    [`password_store_rs::PasswordStore::insert`]).
+///
+/// While the editor runs, `SIGINT` and `SIGQUIT` are left to the editor, and `SIGTERM` or
+/// `SIGHUP` remove the temporary file before the process exits.
+///
+/// # Arguments
+///
+/// * `pass_name` - The name of the password entry to edit.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The `pass_name` contains suspicious path segments.
+/// - The existing entry cannot be decrypted.
+/// - The temporary file cannot be created, or the editor cannot be run or exits with an error.
+/// - Encrypting or committing the edited entry fails.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// cmd_edit("example.com")?;
+/// ```
+pub fn cmd_edit(pass_name: &str) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+
+    let store = open_store()?;
+    ensure_recipient_keys(&store, pass_name)?;
+    let exists = store.contains(pass_name);
+
+    let original = if exists {
+        store.get(pass_name)?
+    } else {
+        Vec::new()
+    };
+
+    let edited = edit_in_temp_file(pass_name, &original)?;
+
+    if edited == original {
+        if exists {
+            println!("Password for {} unchanged.", pass_name);
+        } else {
+            println!("New password for {} not saved.", pass_name);
+        }
+        return Ok(());
+    }
+
+    let action = if exists { "Edit" } else { "Add" };
+    store.insert(
+        pass_name,
+        &edited,
+        &format!("{} password for {} using editor.", action, pass_name),
+    )
+}
+
+/// Writes `contents` to a secure temporary file, runs the editor on it, and returns the result.
+///
+/// The temporary directory is removed when this function returns, whether the editor
+/// succeeded or not.
+fn edit_in_temp_file(pass_name: &str, contents: &[u8]) -> Result<Vec<u8>, StoreError> {
+    let tmp = SecureTempDir::create()?;
+    let file = tmp.write_file(&format!("{}.txt", pass_name.replace('/', "-")), contents)?;
+
+    // Keep Ctrl+C and Ctrl+\ for the editor, which shares our terminal, and make sure
+    // that termination signals do not leave the plaintext behind.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let ignored = [SIGINT, SIGQUIT]
+        .iter()
+        .map(|&signal| signal_hook::flag::register(signal, Arc::clone(&interrupted)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| StoreError::io("Failed to install signal handlers", e))?;
+    let mut signals = Signals::new([SIGTERM, SIGHUP])
+        .map_err(|e| StoreError::io("Failed to install signal handlers", e))?;
+    let handle = signals.handle();
+    let cleanup_dir = tmp.path().to_path_buf();
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            secure_remove_dir(&cleanup_dir);
+            exit(128 + signal);
+        }
+    });
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor()))
+        .arg("sh")
+        .arg(&file)
+        .status();
+
+    handle.close();
+    for id in ignored {
+        signal_hook::low_level::unregister(id);
+    }
+
+    let status = status.map_err(|e| StoreError::io("Failed to launch editor", e))?;
+    if !status.success() {
+        return Err(StoreError::InvalidInput(format!(
+            "Editor exited with status: {}",
+            status
+        )));
+    }
+
+    fs::read(&file).map_err(|e| StoreError::io(format!("Failed to read {}", file.display()), e))
+}
+
+/// Returns the editor command from `$EDITOR`, `$VISUAL`, or `vi` as a last resort.
+fn editor() -> String {
+    env::var("EDITOR")
+        .or_else(|_| env::var("VISUAL"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// A private temporary directory for decrypted entries that is securely removed on drop.
+///
+/// The directory is created with mode `0700` under `/dev/shm` when it is available, so the
+/// plaintext never reaches a disk. Otherwise the system temporary directory is used and a
+/// warning is printed. Files inside it are created with mode `0600`.
+struct SecureTempDir {
+    path: PathBuf,
+}
+
+impl SecureTempDir {
+    /// Creates a new uniquely named temporary directory.
+    fn create() -> Result<SecureTempDir, StoreError> {
+        let shm = Path::new("/dev/shm");
+        if shm.is_dir() {
+            if let Ok(tmp) = SecureTempDir::create_in(shm) {
+                return Ok(tmp);
+            }
+        }
+
+        eprintln!(
+            "Warning: /dev/shm is not available, so the decrypted entry will be \
+             written to {} and may remain on disk.",
+            env::temp_dir().display()
+        );
+        SecureTempDir::create_in(&env::temp_dir())
+    }
+
+    /// Creates a new uniquely named temporary directory inside `base`.
+    fn create_in(base: &Path) -> Result<SecureTempDir, StoreError> {
+        let mut suffix = [0u8; 8];
+        getrandom::fill(&mut suffix).map_err(|e| {
+            StoreError::io(
+                "Failed to read from the system random number generator",
+                io::Error::other(e.to_string()),
+            )
+        })?;
+        let name: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+        let path = base.join(format!("pass.{}", name));
+
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&path)
+            .map_err(|e| StoreError::io(format!("Failed to create {}", path.display()), e))?;
+
+        Ok(SecureTempDir { path })
+    }
+
+    /// Returns the path of the temporary directory.
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Creates `name` inside the directory with mode `0600` and writes `contents` to it.
+    fn write_file(&self, name: &str, contents: &[u8]) -> Result<PathBuf, StoreError> {
+        let file_path = self.path.join(name);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&file_path)
+            .map_err(|e| StoreError::io(format!("Failed to create {}", file_path.display()), e))?;
+        file.write_all(contents)
+            .map_err(|e| StoreError::io(format!("Failed to write {}", file_path.display()), e))?;
+        Ok(file_path)
+    }
+}
+
+impl Drop for SecureTempDir {
+    fn drop(&mut self) {
+        secure_remove_dir(&self.path);
+    }
+}
+
+/// Overwrites every file in `dir` with zeros, then removes the files and the directory.
+///
+/// Errors are ignored, since this runs during cleanup and there is nothing left to do
+/// about them.
+fn secure_remove_dir(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Ok(metadata) = fs::symlink_metadata(&path) {
+                if metadata.is_file() {
+                    let _ = overwrite_with_zeros(&path, metadata.len());
+                }
+            }
+            let _ = fs::remove_file(&path);
+        }
+    }
+    let _ = fs::remove_dir(dir);
+}
+
+/// Overwrites the first `len` bytes of `path` with zeros and flushes them to storage.
+fn overwrite_with_zeros(path: &Path, len: u64) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 4096];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
@@ -0,0 +1,240 @@
+use crate::commands::add::ensure_recipient_keys;
+use crate::commands::{copy_to_clipboard, open_store};
+use password_store_rs::utils::{check_sneaky_paths, yesno};
+use password_store_rs::StoreError;
+use std::env;
+use std::io;
+
+/// Password length used when neither an explicit length nor `PASSWORD_STORE_GENERATED_LENGTH` is given.
+const DEFAULT_GENERATED_LENGTH: usize = 25;
+
+/// Character set used when `PASSWORD_STORE_CHARACTER_SET` is not set.
+const DEFAULT_CHARACTER_SET: &str = "[:punct:][:alnum:]";
+
+/// Character set used with `--no-symbols` when `PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS` is not set.
+const DEFAULT_CHARACTER_SET_NO_SYMBOLS: &str = "[:alnum:]";
+
+/// Generates a new random password and stores it in the password store, similar to pass's cmd_generate.
+///
+/// This function performs the following steps:
+/// 1. Validates `pass_name` with [`check_sneaky_paths`] and determines the password length, either
+///    from `length` or from `PASSWORD_STORE_GENERATED_LENGTH` (default 25).
+/// 2. Expands the character set from `PASSWORD_STORE_CHARACTER_SET` (default `[:punct:][:alnum:]`),
+///    or from `PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS` (default `[:alnum:]`) when `no_symbols` is set.
+/// 3. Draws the password from the operating system's CSPRNG.
+/// 4. Writes the entry:
+///    - **In-place mode (`in_place == true`)**: decrypts the existing entry and replaces only its
+///      first line, keeping any additional lines.
+///    - Otherwise, writes a new entry, asking for confirmation before overwriting unless `force` is set.
+/// 5. Encrypts and commits the entry through the same path as `add` (see
+///    [`password_store_rs::PasswordStore::insert`]).
+/// 6. Prints the password, or puts it on the clipboard when `clip` is set.
+///
+/// # Arguments
+///
+/// * `pass_name` - The name of the password entry.
+/// * `length` - An optional password length overriding `PASSWORD_STORE_GENERATED_LENGTH`.
+/// * `no_symbols` - If true, the password contains only alphanumeric characters.
+/// * `in_place` - If true, only the first line of an existing entry is replaced.
+/// * `force` - If true, an existing entry is overwritten without prompting.
+/// * `clip` - If true, the password is put on the clipboard instead of being printed.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The length is zero or `PASSWORD_STORE_GENERATED_LENGTH` is not a number.
+/// - The character set is empty.
+/// - `in_place` is set and the entry does not exist or cannot be decrypted.
+/// - Encryption, Git or clipboard operations fail.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Generate a 32 character password for "example.com".
+/// cmd_generate("example.com", Some(32), false, false, false, false)?;
+///
+/// // Replace the password of an existing entry, keeping its metadata, and copy it.
+/// cmd_generate("example.com", None, false, true, false, true)?;
+/// ```
+pub fn cmd_generate(
+    pass_name: &str,
+    length: Option<usize>,
+    no_symbols: bool,
+    in_place: bool,
+    force: bool,
+    clip: bool,
+) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+
+    let length = match length {
+        Some(length) => length,
+        None => match env::var("PASSWORD_STORE_GENERATED_LENGTH") {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                StoreError::InvalidInput(format!("pass-length \"{}\" must be a number.", value))
+            })?,
+            Err(_) => DEFAULT_GENERATED_LENGTH,
+        },
+    };
+    if length == 0 {
+        return Err(StoreError::InvalidInput(
+            "pass-length must be greater than zero.".to_string(),
+        ));
+    }
+
+    let charset_spec = if no_symbols {
+        env::var("PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS")
+            .unwrap_or_else(|_| DEFAULT_CHARACTER_SET_NO_SYMBOLS.to_string())
+    } else {
+        env::var("PASSWORD_STORE_CHARACTER_SET")
+            .unwrap_or_else(|_| DEFAULT_CHARACTER_SET.to_string())
+    };
+    let charset = expand_character_set(&charset_spec);
+    if charset.is_empty() {
+        return Err(StoreError::InvalidInput(format!(
+            "character set '{}' is empty.",
+            charset_spec
+        )));
+    }
+
+    let store = open_store()?;
+    ensure_recipient_keys(&store, pass_name)?;
+
+    if in_place {
+        if !store.contains(pass_name) {
+            return Err(StoreError::EntryNotFound(pass_name.to_string()));
+        }
+    } else if !force
+        && store.contains(pass_name)
+        && !yesno(&format!(
+            "An entry already exists for {}. Overwrite it?",
+            pass_name
+        ))
+    {
+        println!("Aborting.");
+        return Ok(());
+    }
+
+    let password = random_password(&charset, length)?;
+
+    let (contents, message) = if in_place {
+        let mut entry = store.entry(pass_name)?;
+        entry.set_password(password.as_str());
+        (
+            entry.to_string(),
+            format!("Replace generated password for {}.", pass_name),
+        )
+    } else {
+        (
+            password.clone(),
+            format!("Add generated password for {}.", pass_name),
+        )
+    };
+
+    store.insert(pass_name, contents.as_bytes(), &message)?;
+
+    if clip {
+        let seconds = copy_to_clipboard(password.as_bytes())?;
+        println!(
+            "Copied {} to clipboard. Will clear in {} seconds.",
+            pass_name, seconds
+        );
+    } else {
+        println!("The generated password for {} is:\n{}", pass_name, password);
+    }
+    Ok(())
+}
+
+/// Expands a `tr`-style character set specification into the list of allowed characters.
+///
+/// The specification supports POSIX classes (`[:alnum:]`, `[:alpha:]`, `[:digit:]`, `[:lower:]`,
+/// `[:upper:]`, `[:punct:]`, `[:xdigit:]`, `[:graph:]`), ranges such as `a-z`, and literal
+/// characters. Only printable ASCII characters are kept and duplicates are removed.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(expand_character_set("[:digit:]"), b"0123456789".to_vec());
+/// assert_eq!(expand_character_set("a-cX"), b"Xabc".to_vec());
+/// ```
+fn expand_character_set(spec: &str) -> Vec<u8> {
+    let bytes = spec.as_bytes();
+    let mut allowed = [false; 128];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'[' && bytes.get(i + 1) == Some(&b':') {
+            if let Some(end) = spec[i + 2..].find(":]") {
+                let class = &spec[i + 2..i + 2 + end];
+                for c in 0x21u8..0x7f {
+                    let matches = match class {
+                        "alnum" => c.is_ascii_alphanumeric(),
+                        "alpha" => c.is_ascii_alphabetic(),
+                        "digit" => c.is_ascii_digit(),
+                        "lower" => c.is_ascii_lowercase(),
+                        "upper" => c.is_ascii_uppercase(),
+                        "punct" => c.is_ascii_punctuation(),
+                        "xdigit" => c.is_ascii_hexdigit(),
+                        "graph" => c.is_ascii_graphic(),
+                        _ => false,
+                    };
+                    if matches {
+                        allowed[c as usize] = true;
+                    }
+                }
+                i += end + 4;
+                continue;
+            }
+        }
+
+        if i + 2 < bytes.len() && bytes[i + 1] == b'-' && bytes[i] <= bytes[i + 2] {
+            for c in bytes[i]..=bytes[i + 2] {
+                if c.is_ascii_graphic() || c == b' ' {
+                    allowed[c as usize] = true;
+                }
+            }
+            i += 3;
+            continue;
+        }
+
+        if bytes[i].is_ascii_graphic() || bytes[i] == b' ' {
+            allowed[bytes[i] as usize] = true;
+        }
+        i += 1;
+    }
+
+    (0u8..128).filter(|&c| allowed[c as usize]).collect()
+}
+
+/// Builds a password of `length` characters drawn uniformly from `charset` using the OS CSPRNG.
+///
+/// Random bytes that would bias the distribution (those at or above the largest multiple of
+/// the character set size) are rejected and redrawn.
+///
+/// # Returns
+///
+/// * `Ok(String)` with the generated password.
+/// * `Err(StoreError)` if the operating system's random number generator fails.
+fn random_password(charset: &[u8], length: usize) -> Result<String, StoreError> {
+    let limit = 256 - (256 % charset.len());
+    let mut password = String::with_capacity(length);
+    let mut buffer = [0u8; 64];
+
+    while password.len() < length {
+        getrandom::fill(&mut buffer).map_err(|e| {
+            StoreError::io(
+                "Failed to read from the system random number generator",
+                io::Error::other(e.to_string()),
+            )
+        })?;
+        for &byte in buffer.iter() {
+            if (byte as usize) < limit {
+                password.push(charset[byte as usize % charset.len()] as char);
+                if password.len() == length {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(password)
+}
@@ -0,0 +1,114 @@
+use crate::commands::open_store;
+use password_store_rs::integrations::git::{git_add_file, git_commit_paths, is_git_repo};
+use password_store_rs::integrations::gpg::{gpg_executable, gpg_options};
+use password_store_rs::StoreError;
+use std::fs;
+use std::path::Path;
+use std::process::{exit, Command};
+
+/// Runs a Git command inside the password store, similar to pass's cmd_git.
+///
+/// The arguments are passed through to `git`, which runs with the store root as its working
+/// directory and inherits the store's environment. The process exits with Git's exit code.
+///
+/// `git init` is special-cased. After the repository is created, this function:
+/// 1. Commits all existing contents of the store.
+/// 2. Writes a `.gitattributes` file containing `*.gpg diff=gpg` and commits it.
+/// 3. Configures the `gpg` diff driver so that `git diff` and `git log -p` show
+///    decrypted contents (`diff.gpg.binary` and `diff.gpg.textconv`). The textconv command
+///    runs [`gpg_executable`] with [`gpg_options`], like every other GPG call.
+///
+/// Any other command requires the store to already be a Git repository.
+///
+/// # Arguments
+///
+/// * `args` - The arguments to pass to `git`, e.g. `["push", "origin", "main"]`.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The password store does not exist.
+/// - The store is not a Git repository and the command is not `init`.
+/// - `git` cannot be executed, or any step of `git init` fails.
+///
+/// If Git itself exits with a non-success status, the process exits with the same status.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// cmd_git(&["init".to_string()])?;
+/// cmd_git(&["log".to_string(), "--oneline".to_string()])?;
+/// ```
+pub fn cmd_git(args: &[String]) -> Result<(), StoreError> {
+    let store = open_store()?;
+    let store = store.root();
+
+    let is_init = args.first().map(String::as_str) == Some("init");
+    if !is_init && !is_git_repo(store) {
+        return Err(StoreError::GitFailed {
+            context: "the password store is not a git repository. Try \"pass git init\"."
+                .to_string(),
+            status: None,
+            stderr: String::new(),
+        });
+    }
+
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(store)
+        .status()
+        .map_err(|e| StoreError::GitFailed {
+            context: "Failed to execute git".to_string(),
+            status: None,
+            stderr: e.to_string(),
+        })?;
+    if !status.success() {
+        exit(status.code().unwrap_or(1));
+    }
+
+    if is_init {
+        configure_new_repository(store)?;
+    }
+    Ok(())
+}
+
+/// Commits the existing store and sets up the `gpg` diff driver in a freshly initialized repository.
+fn configure_new_repository(store: &Path) -> Result<(), StoreError> {
+    git_commit_paths(
+        store,
+        &[&store.to_string_lossy()],
+        "Add current contents of password store.",
+    )?;
+
+    let attributes = store.join(".gitattributes");
+    fs::write(&attributes, "*.gpg diff=gpg\n")
+        .map_err(|e| StoreError::io(format!("Error writing {}", attributes.display()), e))?;
+    git_add_file(
+        &attributes.to_string_lossy(),
+        "Configure git repository for gpg file diff.",
+    )?;
+
+    let textconv = format!("{} -d {}", gpg_executable(), gpg_options().join(" "));
+    for (key, value) in [
+        ("diff.gpg.binary", "true"),
+        ("diff.gpg.textconv", &textconv),
+    ] {
+        let output = Command::new("git")
+            .args(["config", "--local", key, value])
+            .current_dir(store)
+            .output()
+            .map_err(|e| StoreError::GitFailed {
+                context: "Failed to execute git config".to_string(),
+                status: None,
+                stderr: e.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(StoreError::git(
+                format!("git config {} failed", key),
+                output.status,
+                &output.stderr,
+            ));
+        }
+    }
+    Ok(())
+}
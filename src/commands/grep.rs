@@ -0,0 +1,133 @@
+use crate::commands::open_store;
+use password_store_rs::utils::parallel_map;
+use password_store_rs::{PasswordStore, StoreError};
+use regex::{Regex, RegexBuilder};
+use std::io::IsTerminal;
+use std::process::exit;
+
+/// The matching lines of one entry, or the error that prevented searching it.
+type SearchResult = Result<Vec<String>, StoreError>;
+
+/// Searches the decrypted contents of every password entry, similar to pass's cmd_grep.
+///
+/// This function performs the following steps:
+///
+/// 1. Builds a [`regex::Regex`] from `pattern`, honoring `ignore_case`, `fixed_strings` and
+///    `word_regexp`.
+/// 2. Lists every entry of the store, skipping hidden directories such as `.git`
+///    (see [`PasswordStore::list`]).
+/// 3. Decrypts the entries in parallel with a bounded pool of worker threads, using
+///    [`PasswordStore::get`], and matches the regex against each line.
+/// 4. Prints the matching lines grouped under each entry name, in sorted order. When standard
+///    output is a terminal, entry names and matches are colored.
+///
+/// Entries that fail to decrypt are reported on standard error and skipped.
+///
+/// # Arguments
+///
+/// * `pattern` - The regular expression to search for.
+/// * `ignore_case` - If true, matching is case-insensitive.
+/// * `fixed_strings` - If true, `pattern` is matched literally instead of as a regex.
+/// * `word_regexp` - If true, only matches forming whole words are reported.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The password store does not exist.
+/// - `pattern` is not a valid regular expression.
+///
+/// If nothing matched, the process exits with status 1, like `grep`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Find every entry mentioning a username, ignoring case.
+/// cmd_grep("username: alice", true, false, false)?;
+/// ```
+pub fn cmd_grep(
+    pattern: &str,
+    ignore_case: bool,
+    fixed_strings: bool,
+    word_regexp: bool,
+) -> Result<(), StoreError> {
+    let store = open_store()?;
+
+    let mut source = if fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    if word_regexp {
+        source = format!(r"\b(?:{})\b", source);
+    }
+    let re = RegexBuilder::new(&source)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| StoreError::InvalidInput(format!("invalid pattern: {}", e)))?;
+
+    let names = store.list()?;
+
+    let results = search_entries(&store, &names, &re);
+    let color = std::io::stdout().is_terminal();
+    let mut found = false;
+
+    for (name, result) in names.iter().zip(results) {
+        let lines = match result {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        found = true;
+
+        println!("{}", entry_heading(name, color));
+        for line in lines {
+            if color {
+                println!("{}", re.replace_all(&line, "\x1b[1;31m$0\x1b[0m"));
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    if !found {
+        exit(1);
+    }
+    Ok(())
+}
+
+/// Decrypts the entries `names` on a bounded pool of worker threads and returns the matching
+/// lines of each.
+///
+/// Results are returned in the same order as `names`.
+fn search_entries(store: &PasswordStore, names: &[String], re: &Regex) -> Vec<SearchResult> {
+    parallel_map(names, |name| {
+        store.get(name).map(|contents| {
+            String::from_utf8_lossy(&contents)
+                .lines()
+                .filter(|line| re.is_match(line))
+                .map(str::to_string)
+                .collect()
+        })
+    })
+}
+
+/// Formats the heading printed above the matches of an entry, e.g. `web/example.com:`.
+///
+/// With `color`, the directory part is shown in blue and the entry name in bold.
+fn entry_heading(name: &str, color: bool) -> String {
+    let (dir, base) = match name.rfind('/') {
+        Some(i) => name.split_at(i + 1),
+        None => ("", name),
+    };
+
+    if color {
+        format!("\x1b[94m{}\x1b[1m{}\x1b[0m:", dir, base)
+    } else {
+        format!("{}{}:", dir, base)
+    }
+}
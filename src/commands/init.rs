@@ -1,153 +1,321 @@
 use std::fs;
-use std::process::{exit, Command};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-use crate::integrations::git::git_add_file;
-use crate::integrations::gpg::{generate_new_gpg_key, reencrypt_path, sign_file};
-use crate::utils::PREFIX;
+use password_store_rs::crypto::default_backend;
+use password_store_rs::integrations::age::{AgeBackend, AGE_RECIPIENTS_FILE};
+use password_store_rs::integrations::git::{git_add_file, git_commit_paths};
+use password_store_rs::integrations::gpg::{ReencryptProgress, ReencryptReport};
+use password_store_rs::journal::ReencryptJournal;
+use password_store_rs::utils::{check_sneaky_paths, store_dir};
+use password_store_rs::{CryptoBackend, PasswordStore, StoreError};
 
-/// Initializes a new password store by creating a `.gpg-id` file with the specified or generated GPG key.
+/// Initializes a new password store by creating a `.gpg-id` file with the specified or generated GPG keys.
 ///
 /// This function performs the equivalent of the `pass init` command. It sets up a
 /// password store directory (optionally scoped to a subfolder) and configures it
-/// with a GPG key ID used for encryption. If no GPG ID is provided or the `--auto`
-/// flag is set, a new key is generated interactively using GPG.
+/// with the GPG key IDs used for encryption, one per line. If no GPG ID is provided
+/// or the `--auto` flag is set, a new key is generated interactively using GPG. Keys are
+/// looked up, generated and signed with through the default [`CryptoBackend`].
+///
+/// With `age`, the store is set up for [`AgeBackend`] instead: the inputs are age public keys
+/// (`age1...`), they are written to `.age-recipients`, and no key is generated or signed.
+/// A store uses either GPG or age throughout, so a folder cannot be initialized for the
+/// other one.
 ///
 /// # Arguments
 ///
-/// * `gpg_id_input` - An optional GPG key identifier as a string slice. If `None`
-///   or if the key is not found in the keyring, a new key will be generated.
+/// * `gpg_id_inputs` - The GPG key identifiers to encrypt for. If empty, a new key
+///   will be generated. Any key that is not found in the keyring is replaced by a
+///   newly generated key.
 /// * `subfolder` - An optional subfolder under the password store root. If non-empty,
 ///   the `.gpg-id` will be placed in this subdirectory.
 /// * `auto` - A boolean flag indicating whether to force GPG key generation even
 ///   if a GPG ID is provided.
+/// * `age` - Whether `gpg_id_inputs` are age recipients rather than GPG keys.
+/// * `dry_run` - If true, only print which entries would be re-encrypted, without writing
+///   anything. The keys must already exist.
 ///
 /// # Behavior
 ///
 /// - Ensures the password store directory exists (creates it if needed).
-/// - Writes the GPG ID to a `.gpg-id` file inside the store.
+/// - Writes the GPG IDs to a `.gpg-id` file inside the store, one per line.
 /// - Optionally signs the `.gpg-id` file using `PASSWORD_STORE_SIGNING_KEY`.
-/// - Re-encrypts the store contents (if applicable).
+/// - Re-encrypts the entries that are not yet encrypted for the new keys in parallel, with a
+///   progress bar on standard error when it is a terminal, and prints a summary. Entries that
+///   fail are listed and keep their old encryption; the others are still re-encrypted.
 /// - Stages changes in Git, if Git is enabled.
 ///
-/// # Panics / Exits
+/// # Errors
 ///
-/// This function terminates the program (`exit(1)`) if:
+/// Returns a [`StoreError`] if:
+/// - `subfolder` points outside the store (see [`check_sneaky_paths`]).
 /// - GPG commands fail to run or return errors.
 /// - Filesystem operations fail (creating directories, writing files).
 /// - Git operations fail (e.g. staging files).
 /// - GPG key generation or fingerprint extraction fails.
+/// - With `age`, no recipient is given or one is not a valid age public key.
+/// - The store already uses the other backend.
+/// - A re-encryption of another folder did not finish (see
+///   [`ReencryptJournal::check_unfinished`]). Nothing is changed in that case.
+/// - With `dry_run`, a key would have to be generated.
+/// - Some entries could not be re-encrypted. The rest of the store is re-encrypted and
+///   committed first.
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,ignore
 /// // Initialize with an existing key
-/// cmd_init(Some(\"34E8F4A6A3851A5C\"), \"\", false);
+/// cmd_init(&["34E8F4A6A3851A5C"], "", false, false, false)?;
+///
+/// // Share a subfolder between two keys
+/// cmd_init(&["34E8F4A6A3851A5C", "alice@example.com"], "team", false, false, false)?;
 ///
 /// // Initialize with a new key
-/// cmd_init(None, \"my/project\", true);
+/// cmd_init(&[], "my/project", true, false, false)?;
+///
+/// // Initialize an age store
+/// cmd_init(&["age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"], "", false, true, false)?;
+///
+/// // See which entries adding a second key would re-encrypt
+/// cmd_init(&["34E8F4A6A3851A5C", "alice@example.com"], "", false, false, true)?;
 /// ```
-pub fn cmd_init(gpg_id_input: Option<&str>, subfolder: &str, auto: bool) {
+pub fn cmd_init(
+    gpg_id_inputs: &[&str],
+    subfolder: &str,
+    auto: bool,
+    age: bool,
+    dry_run: bool,
+) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![subfolder])?;
     println!("Initialize new password storage");
 
+    let root = store_dir()?;
     let store_dir = if subfolder.is_empty() {
-        format!("{}", &*PREFIX)
+        root.display().to_string()
     } else {
-        format!("{}/{}", &*PREFIX, subfolder)
+        format!("{}/{}", root.display(), subfolder)
     };
 
-    let gpg_id_file = format!("{}/.gpg-id", store_dir);
+    let backend: Box<dyn CryptoBackend> = if age {
+        Box::new(AgeBackend::from_env())
+    } else {
+        default_backend()?
+    };
+    let (id_file_name, id_kind) = if age {
+        (AGE_RECIPIENTS_FILE, "age recipients")
+    } else {
+        (".gpg-id", "GPG id")
+    };
+    let gpg_id_file = format!("{}/{}", store_dir, id_file_name);
 
-    let key_id = if auto || gpg_id_input.is_none() {
+    let other_id_file = if age { ".gpg-id" } else { AGE_RECIPIENTS_FILE };
+    if root.join(other_id_file).is_file() {
+        return Err(StoreError::InvalidInput(format!(
+            "{} is set up with {}. A store cannot mix GPG and age.",
+            root.display(),
+            other_id_file
+        )));
+    }
+
+    let key_ids: Vec<String> = if age {
+        if gpg_id_inputs.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "Usage: pass init --age age-recipient...".to_string(),
+            ));
+        }
+        gpg_id_inputs
+            .iter()
+            .map(|input| {
+                let provided = input.trim();
+                if backend.key_exists(provided)? {
+                    Ok(provided.to_string())
+                } else {
+                    Err(StoreError::RecipientMissing(format!(
+                        "'{}' is not a valid age recipient.",
+                        provided
+                    )))
+                }
+            })
+            .collect::<Result<_, _>>()?
+    } else if dry_run && (auto || gpg_id_inputs.is_empty()) {
+        return Err(StoreError::InvalidInput(
+            "--dry-run cannot generate a new GPG key. Give the keys to initialize with."
+                .to_string(),
+        ));
+    } else if auto || gpg_id_inputs.is_empty() {
         println!("No GPG ID provided or auto flag set. Generating a new GPG key...");
-        generate_new_gpg_key()
+        vec![backend.generate_key()?]
     } else {
-        let provided = gpg_id_input.unwrap().trim();
-        let output = Command::new("gpg")
-            .arg("--list-keys")
-            .arg(provided)
-            .output()
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to execute gpg --list-keys: {}", e);
-                exit(1);
-            });
-
-        if output.stdout.is_empty() {
-            println!(
-                "Provided key '{}' not found. Generating a new key...",
-                provided
-            );
-            generate_new_gpg_key()
-        } else {
-            provided.to_string()
-        }
+        gpg_id_inputs
+            .iter()
+            .map(|input| {
+                let provided = input.trim();
+                if !backend.key_exists(provided)? {
+                    if dry_run {
+                        return Err(StoreError::RecipientMissing(format!(
+                            "No public key for '{}' found.",
+                            provided
+                        )));
+                    }
+                    println!(
+                        "Provided key '{}' not found. Generating a new key...",
+                        provided
+                    );
+                    backend.generate_key()
+                } else {
+                    Ok(provided.to_string())
+                }
+            })
+            .collect::<Result<_, _>>()?
+    };
+    let key_id = key_ids.join(", ");
+    let scope = if subfolder.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", subfolder)
     };
 
-    if let Err(e) = fs::create_dir_all(&store_dir) {
-        eprintln!("Error creating directory {}: {}", store_dir, e);
-        exit(1);
+    if dry_run {
+        println!("Would set {} to {}{}", id_kind, key_id, scope);
+        return print_stale_entries(root, backend, subfolder, &key_ids);
     }
 
-    if let Err(e) = fs::write(&gpg_id_file, format!("{}\n", key_id)) {
-        eprintln!("Error writing .gpg-id file {}: {}", gpg_id_file, e);
-        exit(1);
-    }
-    println!(
-        "Password store initialized for {}{}",
-        key_id,
-        if subfolder.is_empty() {
-            "".to_string()
-        } else {
-            format!(" ({})", subfolder)
-        }
-    );
+    ReencryptJournal::check_unfinished(&root, Path::new(&store_dir))?;
+    fs::create_dir_all(&store_dir)
+        .map_err(|e| StoreError::io(format!("Error creating directory {}", store_dir), e))?;
+
+    let contents: String = key_ids.iter().map(|id| format!("{}\n", id)).collect();
+    fs::write(&gpg_id_file, contents).map_err(|e| {
+        StoreError::io(
+            format!("Error writing {} file {}", id_file_name, gpg_id_file),
+            e,
+        )
+    })?;
+    println!("Password store initialized for {}{}", key_id, scope);
 
-    if let Err(e) = git_add_file(
+    git_add_file(
         &gpg_id_file,
-        &format!(
-            "Set GPG id to {}{}",
-            key_id,
-            if subfolder.is_empty() {
-                "".to_string()
-            } else {
-                format!(" ({})", subfolder)
-            }
-        ),
-    ) {
-        eprintln!("Error adding {} to git: {}", gpg_id_file, e);
-        exit(1);
-    }
+        &format!("Set {} to {}{}", id_kind, key_id, scope),
+    )?;
 
     if let Ok(signing_keys) = std::env::var("PASSWORD_STORE_SIGNING_KEY") {
         if !signing_keys.trim().is_empty() {
-            if let Err(e) = sign_file(&gpg_id_file) {
-                eprintln!("Could not sign .gpg-id: {}", e);
-                exit(1);
-            }
-            println!("Signed .gpg-id file.");
-            if let Err(e) = git_add_file(
+            let keys: Vec<String> = signing_keys.split_whitespace().map(String::from).collect();
+            backend.sign(Path::new(&gpg_id_file), &keys)?;
+            println!("Signed {} file.", id_file_name);
+            git_add_file(
                 &(gpg_id_file.clone() + ".sig"),
                 &format!(
-                    "Signing new GPG id with {}",
+                    "Signing new {} with {}",
+                    id_kind,
                     signing_keys.replace(" ", ", ")
                 ),
-            ) {
-                eprintln!("Error adding {}.sig to git: {}", gpg_id_file, e);
-                exit(1);
-            }
+            )?;
+        }
+    }
+
+    let store = PasswordStore::with_backend(root, backend)?;
+    let show_progress = io::stderr().is_terminal();
+    let report = store.reencrypt(subfolder, &|progress| {
+        if show_progress {
+            print_progress(progress);
         }
+    })?;
+    print_summary(&store, &report);
+
+    git_commit_paths(
+        store.root(),
+        &[&store_dir],
+        &format!("Reencrypt password store using new {} {}", id_kind, key_id),
+    )?;
+
+    if !report.failed.is_empty() {
+        return Err(StoreError::backend(
+            store.backend().name(),
+            format!(
+                "Could not re-encrypt {}. Run the same init again to retry",
+                entry_count(report.failed.len())
+            ),
+            "",
+        ));
     }
+    Ok(())
+}
 
-    if let Err(e) = reencrypt_path(&store_dir) {
-        eprintln!("Error reencrypting path {}: {}", store_dir, e);
-        exit(1);
+/// Redraws the re-encryption progress bar on standard error, e.g.
+/// `[##########          ] 120/240, 2 failed`.
+///
+/// The bar is ended with a newline once the last entry is done.
+fn print_progress(progress: ReencryptProgress) {
+    const WIDTH: usize = 30;
+    let filled = WIDTH * progress.done / progress.total.max(1);
+    let mut line = format!(
+        "\r[{}{}] {}/{}",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        progress.done,
+        progress.total
+    );
+    if progress.failed > 0 {
+        line.push_str(&format!(", {} failed", progress.failed));
+    }
+    if progress.done == progress.total {
+        line.push('\n');
+    }
+    let mut stderr = io::stderr();
+    let _ = stderr.write_all(line.as_bytes());
+    let _ = stderr.flush();
+}
+
+/// Prints how many entries were re-encrypted and skipped, and every entry that failed with
+/// its error on standard error.
+///
+/// Nothing is printed for a folder without entries.
+fn print_summary(store: &PasswordStore, report: &ReencryptReport) {
+    if report.reencrypted.is_empty() && report.skipped == 0 && report.failed.is_empty() {
+        return;
     }
+    println!(
+        "Re-encrypted {}, skipped {} already up to date.",
+        entry_count(report.reencrypted.len()),
+        entry_count(report.skipped)
+    );
+    for (entry, e) in &report.failed {
+        eprintln!("Failed to re-encrypt {}: {}", store.entry_name(entry), e);
+    }
+}
 
-    if let Err(e) = git_add_file(
-        &store_dir,
-        &format!("Reencrypt password store using new GPG id {}", key_id),
-    ) {
-        eprintln!("Error adding {} to git: {}", store_dir, e);
-        exit(1);
+/// Prints the entries in `subfolder` that would be re-encrypted for `key_ids`, for `--dry-run`.
+///
+/// Nothing is written. A store that does not exist yet has no entries to re-encrypt.
+fn print_stale_entries(
+    root: PathBuf,
+    backend: Box<dyn CryptoBackend>,
+    subfolder: &str,
+    key_ids: &[String],
+) -> Result<(), StoreError> {
+    let stale = if root.is_dir() {
+        PasswordStore::with_backend(root, backend)?.stale_entries(subfolder, key_ids)?
+    } else {
+        Vec::new()
+    };
+
+    for name in &stale {
+        println!("Would re-encrypt {}", name);
+    }
+    match stale.len() {
+        0 => println!("No entries would be re-encrypted."),
+        1 => println!("1 entry would be re-encrypted."),
+        count => println!("{} entries would be re-encrypted.", count),
     }
+    Ok(())
 }
 
+/// Formats `n` as `1 entry` or `n entries`.
+fn entry_count(n: usize) -> String {
+    match n {
+        1 => "1 entry".to_string(),
+        n => format!("{} entries", n),
+    }
+}
@@ -1,4 +1,105 @@
 pub mod add;
+pub mod copy_move;
+pub mod edit;
 pub mod find;
+pub mod generate;
+pub mod git;
+pub mod grep;
 pub mod init;
+pub mod otp;
+pub mod rm;
 pub mod show;
+
+use std::env;
+use std::process::Command;
+
+use password_store_rs::integrations::clipboard::copy_with_timeout;
+use password_store_rs::utils::store_dir;
+use password_store_rs::{PasswordStore, StoreError};
+use serde::Serialize;
+
+/// Name of the hidden subcommand that runs the background clipboard clearer.
+pub const CLIP_RESTORE_SUBCOMMAND: &str = "__clip-restore";
+
+/// Opens the password store at `PASSWORD_STORE_DIR`, or `$HOME/.password-store` (see [`store_dir`]).
+///
+/// # Errors
+///
+/// Returns [`StoreError::NotInitialized`] if the password store does not exist.
+pub fn open_store() -> Result<PasswordStore, StoreError> {
+    PasswordStore::open(store_dir()?)
+}
+
+/// Copies `secret` to the clipboard until `PASSWORD_STORE_CLIP_TIME` seconds have passed.
+///
+/// The clipboard is cleared by a detached copy of this executable running the hidden
+/// [`CLIP_RESTORE_SUBCOMMAND`] (see [`copy_with_timeout`]).
+///
+/// # Returns
+///
+/// * `Ok(u64)` with the number of seconds until the clipboard is cleared.
+/// * `Err(StoreError)` if no clipboard is available or any command fails.
+pub fn copy_to_clipboard(secret: &[u8]) -> Result<u64, StoreError> {
+    let exe = env::current_exe()
+        .map_err(|e| StoreError::io("Failed to locate the current executable", e))?;
+    let mut clearer = Command::new(exe);
+    clearer.arg(CLIP_RESTORE_SUBCOMMAND);
+    copy_with_timeout(secret, clearer)
+}
+
+/// How `show` and `find` print their results, chosen with the global `--format` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Text for people: trees and plain entries.
+    Text,
+    /// JSON for scripts.
+    Json,
+    /// YAML for scripts.
+    Yaml,
+}
+
+impl OutputFormat {
+    /// Returns the format called `name`: `text`, `json` or `yaml`.
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "yaml" => Some(OutputFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Formats `value` as JSON or YAML, or returns `None` for [`OutputFormat::Text`], whose
+    /// output each command writes itself.
+    pub fn serialize<T: Serialize + ?Sized>(self, value: &T) -> Option<String> {
+        // Entries, paths and errors are maps and lists of strings, which always serialize.
+        match self {
+            OutputFormat::Text => None,
+            OutputFormat::Json => {
+                Some(serde_json::to_string_pretty(value).expect("output serializes to JSON") + "\n")
+            }
+            OutputFormat::Yaml => {
+                Some(serde_yaml::to_string(value).expect("output serializes to YAML"))
+            }
+        }
+    }
+
+    /// Prints `error` on standard error: `Error: message` as text, or an object with the
+    /// [`StoreError::kind`] as `error` and the message as `message` in JSON and YAML.
+    pub fn print_error(self, error: &StoreError) {
+        #[derive(Serialize)]
+        struct ErrorOutput {
+            error: &'static str,
+            message: String,
+        }
+
+        let output = ErrorOutput {
+            error: error.kind(),
+            message: error.to_string(),
+        };
+        match self.serialize(&output) {
+            Some(formatted) => eprint!("{}", formatted),
+            None => eprintln!("Error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use crate::commands::add::ensure_recipient_keys;
+use crate::commands::{copy_to_clipboard, open_store};
+use password_store_rs::otp::{find_uri, unix_time, OtpKind, OtpUri};
+use password_store_rs::utils::{check_sneaky_paths, yesno};
+use password_store_rs::{PasswordEntry, PasswordStore, StoreError};
+use std::io::{self, IsTerminal, Write};
+
+/// Prints the current one-time password of an entry, or puts it on the clipboard.
+///
+/// This function implements `otp show` (and plain `otp`), like `pass otp`. It performs the
+/// following steps:
+///
+/// 1. Decrypts the entry and parses the first `otpauth://` URI in it, on any line (see
+///    [`find_uri`] and [`OtpUri::parse`]).
+/// 2. For `otpauth://hotp` URIs, increments the counter in the URI and re-encrypts the entry,
+///    committing it to Git, before any code is computed, so that no code is ever shown twice.
+/// 3. Computes the code: for `otpauth://totp` URIs from the current time (RFC 6238), for
+///    `otpauth://hotp` URIs from the incremented counter (RFC 4226), like `pass otp`.
+/// 4. Prints the code, or with `clip` copies it to the clipboard and clears it after
+///    `PASSWORD_STORE_CLIP_TIME` seconds.
+///
+/// # Arguments
+///
+/// * `pass_name` - The entry that holds the `otpauth://` URI.
+/// * `clip` - If true, the code is put on the clipboard instead of being printed.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The entry does not exist, or its name contains suspicious path segments.
+/// - Decryption fails, or re-encrypting an HOTP entry fails.
+/// - The entry has no `otpauth://` URI, or the URI is invalid.
+/// - Copying to the clipboard fails.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Print the current code for "github".
+/// cmd_otp_show("github", false)?;
+///
+/// // Copy it to the clipboard.
+/// cmd_otp_show("github", true)?;
+/// ```
+pub fn cmd_otp_show(pass_name: &str, clip: bool) -> Result<(), StoreError> {
+    let (store, mut entry, line, mut uri) = open_uri(pass_name)?;
+
+    if let OtpKind::Hotp { .. } = uri.kind() {
+        uri.increment_counter()?;
+        entry.set_line(line, uri.to_string())?;
+        ensure_recipient_keys(&store, pass_name)?;
+        store.insert(
+            pass_name,
+            entry.to_string().as_bytes(),
+            &format!("Increment HOTP counter for {}.", pass_name),
+        )?;
+    }
+
+    let code = uri.code(unix_time()?);
+    if clip {
+        let seconds = copy_to_clipboard(code.as_bytes())?;
+        println!(
+            "Copied OTP code for {} to clipboard. Will clear in {} seconds.",
+            pass_name, seconds
+        );
+    } else {
+        println!("{}", code);
+    }
+    Ok(())
+}
+
+/// Adds an `otpauth://` URI to an entry, like `pass otp insert`.
+///
+/// The URI is taken from `maybe_uri`, or read from standard input when it is not a terminal
+/// (e.g. from a QR code decoder), or else prompted for: hidden with confirmation, or visibly
+/// with `echo`. It is checked with [`OtpUri::parse`] before anything is written.
+///
+/// If the entry exists, the URI replaces its current `otpauth://` line (after confirmation,
+/// unless `force` is set) or is added as a new last line, keeping the password, fields and
+/// notes. Otherwise a new entry is created that holds only the URI. The entry is encrypted
+/// and committed to Git.
+///
+/// # Arguments
+///
+/// * `pass_name` - The entry to add the URI to.
+/// * `maybe_uri` - The `otpauth://` URI, or `None` to read it.
+/// * `echo` - If true, the URI is read with echo.
+/// * `force` - If true, an existing URI in the entry is replaced without prompting.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The name contains suspicious path segments, or the store does not exist.
+/// - The URI cannot be read, the two hidden inputs differ, or the URI is invalid.
+/// - No public key is available for a recipient.
+/// - Decrypting the existing entry or encrypting the new one fails.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Add a TOTP secret to the existing entry "github".
+/// cmd_otp_insert("github", Some("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP"), false, false)?;
+///
+/// // Prompt for the URI.
+/// cmd_otp_insert("bank", None, false, false)?;
+/// ```
+pub fn cmd_otp_insert(
+    pass_name: &str,
+    maybe_uri: Option<&str>,
+    echo: bool,
+    force: bool,
+) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+    let store = open_store()?;
+
+    let uri = match maybe_uri {
+        Some(uri) => uri.to_string(),
+        None => read_uri(pass_name, echo)?,
+    };
+    let uri = OtpUri::parse(&uri)?;
+    ensure_recipient_keys(&store, pass_name)?;
+
+    let entry = if store.contains(pass_name) {
+        let mut entry = store.entry(pass_name)?;
+        match find_uri(&entry) {
+            Some(_)
+                if !force
+                    && !yesno(&format!(
+                        "An OTP URI already exists for {}. Overwrite it?",
+                        pass_name
+                    )) =>
+            {
+                println!("Aborting.");
+                return Ok(());
+            }
+            Some((line, _)) => entry.set_line(line, uri.to_string())?,
+            None => entry.push_line(uri.to_string())?,
+        }
+        entry
+    } else {
+        PasswordEntry::parse(&format!("{}\n", uri))
+    };
+
+    store.insert(
+        pass_name,
+        entry.to_string().as_bytes(),
+        &format!("Add OTP secret for {} to store.", pass_name),
+    )?;
+
+    println!("OTP secret for '{}' added successfully.", pass_name);
+    Ok(())
+}
+
+/// Prints the `otpauth://` URI of an entry, or puts it on the clipboard, like `pass otp uri`.
+///
+/// # Arguments
+///
+/// * `pass_name` - The entry that holds the URI.
+/// * `clip` - If true, the URI is put on the clipboard instead of being printed.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if the entry does not exist or cannot be decrypted, has no valid
+/// `otpauth://` URI, or copying to the clipboard fails.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Print the URI of "github", e.g. to set up another device.
+/// cmd_otp_uri("github", false)?;
+/// ```
+pub fn cmd_otp_uri(pass_name: &str, clip: bool) -> Result<(), StoreError> {
+    let (_, _, _, uri) = open_uri(pass_name)?;
+
+    if clip {
+        let seconds = copy_to_clipboard(uri.to_string().as_bytes())?;
+        println!(
+            "Copied OTP URI for {} to clipboard. Will clear in {} seconds.",
+            pass_name, seconds
+        );
+    } else {
+        println!("{}", uri);
+    }
+    Ok(())
+}
+
+/// Checks that an entry holds a valid `otpauth://` URI and describes it, like
+/// `pass otp validate`.
+///
+/// The description names the issuer (or label), the type and its parameters, e.g.
+/// `github has a valid OTP URI for GitHub: TOTP, every 30 seconds, SHA1, 6 digits.`
+///
+/// # Arguments
+///
+/// * `pass_name` - The entry to check.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if the entry does not exist or cannot be decrypted, or has no
+/// `otpauth://` URI, or the URI is invalid. The error says what is wrong with it.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// cmd_otp_validate("github")?;
+/// ```
+pub fn cmd_otp_validate(pass_name: &str) -> Result<(), StoreError> {
+    let (_, _, _, uri) = open_uri(pass_name)?;
+
+    let kind = match uri.kind() {
+        OtpKind::Totp { period } => format!("TOTP, every {} seconds", period),
+        OtpKind::Hotp { counter } => format!("HOTP, counter {}", counter),
+    };
+    println!(
+        "{} has a valid OTP URI for {}: {}, {}, {} digits.",
+        pass_name,
+        uri.issuer().unwrap_or(uri.label()),
+        kind,
+        uri.algorithm(),
+        uri.digits()
+    );
+    Ok(())
+}
+
+/// Opens the store and decrypts `pass_name`, and parses its `otpauth://` URI.
+///
+/// # Returns
+///
+/// * `Ok((store, entry, line, uri))` with the line number of the URI in the entry.
+/// * `Err(StoreError)` if the entry does not exist or cannot be decrypted, or has no valid URI.
+fn open_uri(pass_name: &str) -> Result<(PasswordStore, PasswordEntry, usize, OtpUri), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+    let store = open_store()?;
+    if !store.contains(pass_name) {
+        return Err(StoreError::EntryNotFound(pass_name.to_string()));
+    }
+
+    let entry = store.entry(pass_name)?;
+    let (line, uri) = find_uri(&entry)
+        .ok_or_else(|| StoreError::InvalidInput(format!("{} has no otpauth:// URI.", pass_name)))?;
+    let uri = OtpUri::parse(uri)?;
+    Ok((store, entry, line, uri))
+}
+
+/// Reads an `otpauth://` URI for `pass_name` from standard input, prompting when it is a
+/// terminal: hidden with confirmation, or visibly with `echo`.
+fn read_uri(pass_name: &str, echo: bool) -> Result<String, StoreError> {
+    let interactive = io::stdin().is_terminal();
+    if interactive && !echo {
+        let uri = rpassword::prompt_password(format!("Enter otpauth:// URI for {}: ", pass_name))
+            .map_err(|e| StoreError::io("Failed to read the URI", e))?;
+        let uri_again =
+            rpassword::prompt_password(format!("Retype otpauth:// URI for {}: ", pass_name))
+                .map_err(|e| StoreError::io("Failed to read the URI confirmation", e))?;
+        if uri != uri_again {
+            return Err(StoreError::InvalidInput(
+                "the entered URIs do not match.".to_string(),
+            ));
+        }
+        return Ok(uri);
+    }
+
+    if interactive {
+        print!("Enter otpauth:// URI for {}: ", pass_name);
+        let _ = io::stdout().flush();
+    }
+    let mut line = String::new();
+    io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| StoreError::io("Failed to read the URI", e))?;
+    Ok(line.trim().to_string())
+}
@@ -0,0 +1,66 @@
+use crate::commands::open_store;
+use password_store_rs::utils::{check_sneaky_paths, yesno};
+use password_store_rs::StoreError;
+
+/// Removes a password entry or directory from the password store, similar to pass's cmd_delete.
+///
+/// This function performs the following steps:
+///
+/// 1. Validates `pass_name` with [`check_sneaky_paths`].
+/// 2. Checks that `pass_name` is an entry, or a directory and `recursive` is set.
+/// 3. Asks for confirmation unless `force` is set.
+/// 4. Deletes the entry or directory, records the removal as a Git commit, and removes any
+///    parent directories left empty (see [`password_store_rs::PasswordStore::remove`]).
+///
+/// # Arguments
+///
+/// * `pass_name` - The name of the password entry or directory to remove.
+/// * `recursive` - If true, directories are removed with their contents.
+/// * `force` - If true, the entry is removed without prompting.
+///
+/// # Errors
+///
+/// Returns a [`StoreError`] if:
+/// - The `pass_name` contains suspicious path segments.
+/// - The entry does not exist, or is a directory and `recursive` is not set.
+/// - The target lies outside the password store.
+/// - Removing the files or committing the removal fails.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // Remove a single entry after confirmation.
+/// cmd_rm("example.com", false, false)?;
+///
+/// // Remove a whole folder without prompting.
+/// cmd_rm("work", true, true)?;
+/// ```
+pub fn cmd_rm(pass_name: &str, recursive: bool, force: bool) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+
+    let trimmed = pass_name.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Err(StoreError::InvalidInput(
+            "Usage: pass rm [--recursive,-r] [--force,-f] pass-name".to_string(),
+        ));
+    }
+
+    let store = open_store()?;
+    let is_entry = store.contains(trimmed) && !pass_name.ends_with('/');
+
+    // Only ask when there is something to delete; otherwise let the store report why not.
+    if !force
+        && (is_entry || (recursive && store.is_folder(trimmed)))
+        && !yesno(&format!(
+            "Are you sure you would like to delete {}?",
+            pass_name
+        ))
+    {
+        println!("Aborting.");
+        return Ok(());
+    }
+
+    store.remove(pass_name, recursive)?;
+    println!("Removed {}", pass_name);
+    Ok(())
+}
@@ -1,6 +1,19 @@
-use crate::utils::{check_sneaky_paths, print_dir_structure, PREFIX};
-use std::path::Path;
-use std::process::{exit, Command};
+use crate::commands::{copy_to_clipboard, open_store, OutputFormat};
+use password_store_rs::tree::{entry_paths, render, walk};
+use password_store_rs::utils::check_sneaky_paths;
+use password_store_rs::StoreError;
+use std::io::{self, IsTerminal};
+
+/// The part of an entry that `show` prints or puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection<'a> {
+    /// The whole entry.
+    All,
+    /// Line `n`, counting from 1, which is the password.
+    Line(usize),
+    /// The value of the `key: value` field with this key (see [`password_store_rs::PasswordEntry::field`]).
+    Field(&'a str),
+}
 
 /// Displays a password entry or the password store structure.
 ///
@@ -8,60 +21,147 @@ use std::process::{exit, Command};
 ///
 /// 1. Validates the provided `pass_name` by checking for any potentially dangerous path segments using
 ///    [`check_sneaky_paths`].
-/// 2. Constructs the expected file path for the password entry as `<PREFIX>/<pass_name>.gpg`.
-/// 3. If the file exists, it decrypts the password using the GPG command (`gpg -d`) and prints the result.
-/// 4. If the file does not exist:
-///    - If `pass_name` is empty, it prints the entire password store directory structure using
-///      [`print_dir_structure`].
-///    - Otherwise, it prints an error message indicating that the password entry is not found and exits.
+/// 2. Opens the password store (see [`open_store`]).
+/// 3. If the entry exists, it decrypts and parses it (see [`password_store_rs::PasswordStore::entry`])
+///    and picks the `selection`: the whole entry, one line, or the value of one field. When `clip` is set,
+///    the selection is copied to the clipboard and cleared after `PASSWORD_STORE_CLIP_TIME` seconds;
+///    otherwise it is printed. In JSON or YAML, the whole entry is printed as an object with its
+///    `password`, `fields` and `notes`, and a line or field as a string.
+/// 4. If the entry does not exist:
+///    - If `pass_name` is empty, it prints the entire password store directory structure as a
+///      tree (see [`walk`] and [`render`]), down to `depth` levels.
+///    - If `pass_name` is a folder, it prints the structure of that folder the same way.
+///    - In JSON or YAML, it prints the paths of the entries instead, as a list (see [`entry_paths`]).
+///    - Otherwise, it returns [`StoreError::EntryNotFound`].
 ///
 /// # Arguments
 ///
 /// * `pass_name` - A string slice that specifies the name of the password entry to display. When empty,
 ///   the function prints the directory structure of the password store.
+/// * `selection` - The part of the entry to show.
+/// * `clip` - If true, the selection is put on the clipboard instead of being printed.
+/// * `depth` - How many levels of a folder to print, or `None` for all of them. It does not
+///   apply to entries.
+/// * `format` - Whether to print text, JSON or YAML. Clipboard messages are always text.
 ///
-/// # Panics
+/// # Errors
 ///
-/// The function will terminate the process if:
+/// Returns a [`StoreError`] if:
+/// - The password store does not exist.
+/// - There is no entry or folder called `pass_name`.
 /// - The GPG decryption command fails.
-/// - The password store directory does not exist when attempting to list its structure.
 /// - The `pass_name` contains suspicious path segments (as determined by [`check_sneaky_paths`]).
+/// - The entry has no such line or field.
+/// - The requested clipboard line is empty or copying to the clipboard fails.
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,ignore
 /// // To display the decrypted password for "example.com":
-/// cmd_show("example.com");
+/// cmd_show("example.com", Selection::All, false, None, OutputFormat::Text)?;
+///
+/// // To copy the first line of "example.com" to the clipboard:
+/// cmd_show("example.com", Selection::Line(1), true, None, OutputFormat::Text)?;
+///
+/// // To print the username of "example.com":
+/// cmd_show("example.com", Selection::Field("username"), false, None, OutputFormat::Text)?;
 ///
 /// // To list the password store structure:
-/// cmd_show("");
+/// cmd_show("", Selection::All, false, None, OutputFormat::Text)?;
+///
+/// // To print the paths of the entries in "work" as JSON:
+/// cmd_show("work", Selection::All, false, None, OutputFormat::Json)?;
+///
+/// // To list the top-level entries and folders of "work":
+/// cmd_show("work", Selection::All, false, Some(1), OutputFormat::Text)?;
 /// ```
-pub fn cmd_show(pass_name: &str) {
-    check_sneaky_paths(vec![pass_name]);
+pub fn cmd_show(
+    pass_name: &str,
+    selection: Selection,
+    clip: bool,
+    depth: Option<usize>,
+    format: OutputFormat,
+) -> Result<(), StoreError> {
+    check_sneaky_paths(vec![pass_name])?;
+
+    let store = open_store()?;
 
-    let passfile = format!("{}/{}.gpg", &*PREFIX, pass_name);
+    if store.contains(pass_name) {
+        let entry = store.entry(pass_name)?;
+        let selected = match selection {
+            Selection::All => entry.to_string(),
+            Selection::Line(line) => match entry.line(line) {
+                Some(selected) => selected.to_string(),
+                None if clip => String::new(),
+                None => {
+                    return Err(StoreError::InvalidInput(format!(
+                        "{} has no line {}.",
+                        pass_name, line
+                    )))
+                }
+            },
+            Selection::Field(key) => match entry.field(key) {
+                Some(value) => value.to_string(),
+                None => {
+                    return Err(StoreError::InvalidInput(format!(
+                        "{} has no field '{}'.",
+                        pass_name, key
+                    )))
+                }
+            },
+        };
 
-    if Path::new(&passfile).exists() {
-        let output = Command::new("gpg")
-            .arg("-d")
-            .arg(&passfile)
-            .output()
-            .expect("failed to execute gpg");
-        let pass = String::from_utf8_lossy(&output.stdout);
-        println!("{}", pass);
-    } else if Path::new(&*PREFIX).exists() {
-        if pass_name.is_empty() {
-            println!("Password Store");
+        if clip {
+            if selected.is_empty() {
+                return Err(StoreError::InvalidInput(match selection {
+                    Selection::Field(key) => {
+                        format!(
+                            "There is no password to put on the clipboard in field '{}'.",
+                            key
+                        )
+                    }
+                    Selection::Line(line) => format!(
+                        "There is no password to put on the clipboard at line {}.",
+                        line
+                    ),
+                    Selection::All => "There is no password to put on the clipboard.".to_string(),
+                }));
+            }
+            let seconds = copy_to_clipboard(selected.as_bytes())?;
+            println!(
+                "Copied {} to clipboard. Will clear in {} seconds.",
+                pass_name, seconds
+            );
         } else {
-            let trimmed_path = passfile.trim_end_matches('/');
-            println!("{}", trimmed_path);
+            let formatted = match selection {
+                Selection::All => format.serialize(&entry),
+                Selection::Line(_) | Selection::Field(_) => format.serialize(&selected),
+            };
+            match formatted {
+                Some(formatted) => print!("{}", formatted),
+                None => println!("{}", selected),
+            }
+        }
+    } else if pass_name.is_empty() || store.is_folder(pass_name) {
+        let folder = pass_name.trim_end_matches('/');
+        let nodes = walk(&store.root().join(folder), None)?;
+        match format.serialize(&entry_paths(&nodes, folder, depth)) {
+            Some(formatted) => print!("{}", formatted),
+            None => {
+                println!(
+                    "{}",
+                    if folder.is_empty() {
+                        "Password Store"
+                    } else {
+                        folder
+                    }
+                );
+                print!("{}", render(&nodes, io::stdout().is_terminal(), depth));
+            }
         }
-        print_dir_structure(&Path::new(&*PREFIX), "".to_string()).unwrap();
     } else {
-        eprintln!(
-            "Error: Password store '{}' does not exist. Try \"pass init\".",
-            &*PREFIX
-        );
-        exit(1);
+        return Err(StoreError::EntryNotFound(pass_name.to_string()));
     }
+
+    Ok(())
 }
@@ -0,0 +1,264 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::StoreError;
+use crate::integrations::gpg::GpgBackend;
+
+/// The cryptographic operations the password store needs from an OpenPGP implementation.
+///
+/// [`crate::PasswordStore`] and the commands only talk to the backend through this trait,
+/// so the `gpg` subprocess ([`GpgBackend`]) can be replaced, for example by a native
+/// implementation or by an in-memory fake in tests, without touching the command logic.
+///
+/// Implementations must be usable from several threads at once, since `grep` decrypts
+/// entries in parallel.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use password_store_rs::integrations::gpg::GpgBackend;
+/// use password_store_rs::PasswordStore;
+///
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let store = PasswordStore::with_backend("/home/me/.password-store", Box::new(GpgBackend::default()))?;
+/// if !store.backend().key_exists("alice@example.com")? {
+///     eprintln!("No public key for alice@example.com");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait CryptoBackend: Send + Sync {
+    /// Encrypts `contents` for every key in `recipients` and writes the result to `output`,
+    /// replacing any existing file.
+    fn encrypt(
+        &self,
+        contents: &[u8],
+        recipients: &[String],
+        output: &Path,
+    ) -> Result<(), StoreError>;
+
+    /// Decrypts the file at `path` and returns its plaintext.
+    fn decrypt(&self, path: &Path) -> Result<Vec<u8>, StoreError>;
+
+    /// Writes a detached signature of the file at `path` to `<path>.sig`.
+    ///
+    /// `signing_keys` selects the keys to sign with. When it is empty the backend's
+    /// default key is used.
+    fn sign(&self, path: &Path, signing_keys: &[String]) -> Result<(), StoreError>;
+
+    /// Checks the detached `signature` of the file at `path`.
+    ///
+    /// Returns the fingerprints, in upper case, of the signing keys and their primary keys
+    /// for every valid signature. An invalid signature yields an empty set rather than an
+    /// error, so the caller decides which keys it trusts.
+    fn verify(&self, path: &Path, signature: &Path) -> Result<HashSet<String>, StoreError>;
+
+    /// Returns the fingerprints of the primary keys in the keyring, in keyring order.
+    fn list_keys(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Returns whether a public key matching `key_id` (a fingerprint, key ID or user ID)
+    /// is available.
+    fn key_exists(&self, key_id: &str) -> Result<bool, StoreError>;
+
+    /// Interactively generates a new key pair and returns the fingerprint of its primary key.
+    ///
+    /// Backends that cannot generate keys return [`StoreError::InvalidInput`], which is the
+    /// default.
+    fn generate_key(&self) -> Result<String, StoreError> {
+        Err(StoreError::InvalidInput(
+            "this crypto backend cannot generate keys.".to_string(),
+        ))
+    }
+
+    /// Returns the key IDs the file at `path` is encrypted to, as 16 upper-case hex digits.
+    ///
+    /// Together with [`CryptoBackend::recipient_key_ids`], this lets re-encryption skip
+    /// entries that are already encrypted for the right keys. Backends that cannot tell
+    /// return `Ok(None)`, which is the default, and their entries are always re-encrypted.
+    fn encrypted_key_ids(&self, _path: &Path) -> Result<Option<HashSet<String>>, StoreError> {
+        Ok(None)
+    }
+
+    /// Returns the key IDs, as 16 upper-case hex digits, that encrypting for `recipients`
+    /// would encrypt to: the encryption subkey of each recipient's key.
+    ///
+    /// Returns `Ok(None)` if the backend cannot tell, which is the default.
+    fn recipient_key_ids(
+        &self,
+        _recipients: &[String],
+    ) -> Result<Option<HashSet<String>>, StoreError> {
+        Ok(None)
+    }
+
+    /// Returns the name of the backend, such as `gpg` or `age`, as reported in
+    /// [`StoreError::CryptoFailed`].
+    ///
+    /// Defaults to `gpg`.
+    fn name(&self) -> &'static str {
+        "gpg"
+    }
+
+    /// Returns the file extension of encrypted entries, without the leading dot.
+    ///
+    /// Defaults to `gpg`.
+    fn extension(&self) -> &'static str {
+        "gpg"
+    }
+
+    /// Returns the name of the file that lists the recipients of a folder.
+    ///
+    /// Defaults to `.gpg-id`.
+    fn recipients_file(&self) -> &'static str {
+        ".gpg-id"
+    }
+}
+
+/// Returns the backend selected by `PASSWORD_STORE_CRYPTO_BACKEND`.
+///
+/// | Value               | Backend                                                     |
+/// |---------------------|-------------------------------------------------------------|
+/// | unset, `gpg`        | The `gpg` executable ([`GpgBackend`])                       |
+/// | `openpgp`           | The native implementation, with the `openpgp` cargo feature |
+///
+/// # Returns
+///
+/// * `Ok(Box<dyn CryptoBackend>)` with the selected backend.
+/// * `Err(StoreError::InvalidInput)` if the value is unknown, or names a backend this
+///   build does not include.
+/// * `Err(StoreError)` if the backend cannot be set up, e.g. its keyring cannot be read.
+pub fn default_backend() -> Result<Box<dyn CryptoBackend>, StoreError> {
+    let name = std::env::var("PASSWORD_STORE_CRYPTO_BACKEND").unwrap_or_default();
+    match name.trim() {
+        "" | "gpg" => Ok(Box::new(GpgBackend::default())),
+        #[cfg(feature = "openpgp")]
+        "openpgp" => Ok(Box::new(
+            crate::integrations::openpgp::OpenPgpBackend::from_env()?,
+        )),
+        #[cfg(not(feature = "openpgp"))]
+        "openpgp" => Err(StoreError::InvalidInput(
+            "the openpgp backend is not available. Rebuild with \"--features openpgp\"."
+                .to_string(),
+        )),
+        other => Err(StoreError::InvalidInput(format!(
+            "unknown PASSWORD_STORE_CRYPTO_BACKEND '{}'.",
+            other
+        ))),
+    }
+}
+
+/// An in-memory [`CryptoBackend`] for unit tests, so that [`crate::PasswordStore`] can be
+/// exercised without `gpg` or a keyring.
+///
+/// Nothing is actually encrypted: an entry is written as a `fake:` line naming its
+/// recipients, followed by the contents. Only the keys the backend was created with exist,
+/// and encrypting for any other recipient fails like a missing public key would.
+#[cfg(test)]
+pub(crate) mod fake {
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::Path;
+
+    use super::CryptoBackend;
+    use crate::error::StoreError;
+
+    const HEADER: &str = "fake:";
+
+    pub(crate) struct FakeBackend {
+        keys: Vec<String>,
+    }
+
+    impl FakeBackend {
+        /// Creates a backend whose keyring holds `keys`.
+        pub(crate) fn new(keys: &[&str]) -> FakeBackend {
+            FakeBackend {
+                keys: keys.iter().map(|key| key.to_string()).collect(),
+            }
+        }
+
+        /// Returns the recipients the file at `path` was encrypted for, and its contents.
+        pub(crate) fn read(path: &Path) -> Result<(Vec<String>, Vec<u8>), StoreError> {
+            let data = fs::read(path)
+                .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+            let not_encrypted = || {
+                StoreError::backend(
+                    "fake",
+                    format!("Failed to decrypt {}", path.display()),
+                    "not encrypted by the fake backend",
+                )
+            };
+            let newline = data
+                .iter()
+                .position(|&b| b == b'\n')
+                .ok_or_else(not_encrypted)?;
+            let header = std::str::from_utf8(&data[..newline]).map_err(|_| not_encrypted())?;
+            let recipients = header.strip_prefix(HEADER).ok_or_else(not_encrypted)?;
+            Ok((
+                recipients.split(',').map(String::from).collect(),
+                data[newline + 1..].to_vec(),
+            ))
+        }
+    }
+
+    impl CryptoBackend for FakeBackend {
+        fn encrypt(
+            &self,
+            contents: &[u8],
+            recipients: &[String],
+            output: &Path,
+        ) -> Result<(), StoreError> {
+            if let Some(missing) = recipients.iter().find(|r| !self.keys.contains(r)) {
+                return Err(StoreError::RecipientMissing(format!(
+                    "No public key for '{}' found.",
+                    missing
+                )));
+            }
+            let mut data = format!("{}{}\n", HEADER, recipients.join(",")).into_bytes();
+            data.extend_from_slice(contents);
+            fs::write(output, data)
+                .map_err(|e| StoreError::io(format!("Failed to write {}", output.display()), e))
+        }
+
+        fn decrypt(&self, path: &Path) -> Result<Vec<u8>, StoreError> {
+            FakeBackend::read(path).map(|(_, contents)| contents)
+        }
+
+        fn sign(&self, path: &Path, signing_keys: &[String]) -> Result<(), StoreError> {
+            let signature = format!("{}.sig", path.display());
+            fs::write(&signature, signing_keys.join(","))
+                .map_err(|e| StoreError::io(format!("Failed to write {}", signature), e))
+        }
+
+        fn verify(&self, _path: &Path, signature: &Path) -> Result<HashSet<String>, StoreError> {
+            let keys = fs::read_to_string(signature).map_err(|e| {
+                StoreError::io(format!("Failed to read {}", signature.display()), e)
+            })?;
+            Ok(keys.split(',').map(str::to_uppercase).collect())
+        }
+
+        fn list_keys(&self) -> Result<Vec<String>, StoreError> {
+            Ok(self.keys.clone())
+        }
+
+        fn key_exists(&self, key_id: &str) -> Result<bool, StoreError> {
+            Ok(self.keys.iter().any(|key| key == key_id))
+        }
+
+        fn encrypted_key_ids(&self, path: &Path) -> Result<Option<HashSet<String>>, StoreError> {
+            // Like gpg, a file that cannot be read is reported as unknown rather than failing.
+            Ok(FakeBackend::read(path)
+                .ok()
+                .map(|(recipients, _)| recipients.iter().map(|r| r.to_uppercase()).collect()))
+        }
+
+        fn recipient_key_ids(
+            &self,
+            recipients: &[String],
+        ) -> Result<Option<HashSet<String>>, StoreError> {
+            Ok(Some(recipients.iter().map(|r| r.to_uppercase()).collect()))
+        }
+
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::error::StoreError;
+
+/// The decrypted contents of a password entry, read with the pass convention.
+///
+/// The first line is the password. Every following line of the form `key: value` is a field,
+/// such as `username: alice` or `url: https://example.com`, and all other lines are free-form
+/// notes. Keys are matched ignoring case. A line only counts as a field if the `:` is
+/// followed by whitespace or ends the line, so `otpauth://...` and other URIs stay notes.
+///
+/// The entry keeps its lines as they were, including `\r\n` line endings, so its
+/// [`fmt::Display`] form gives back the exact contents it was parsed from, apart from the
+/// lines that were changed. The accessors return lines without the `\r`. It serializes as a
+/// map of its password, fields and notes instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use password_store_rs::PasswordEntry;
+///
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let mut entry = PasswordEntry::parse("hunter2\nusername: alice\nSecurity questions below\n");
+/// assert_eq!(entry.password(), "hunter2");
+/// assert_eq!(entry.field("Username"), Some("alice"));
+/// assert_eq!(entry.notes(), ["Security questions below"]);
+///
+/// // The new contents to store, e.g. with `PasswordStore::insert`.
+/// entry.set_field("url", "https://example.com")?;
+/// assert_eq!(
+///     entry.to_string(),
+///     "hunter2\nusername: alice\nurl: https://example.com\nSecurity questions below\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordEntry {
+    /// The first line, with its `\r` if it had one.
+    password: String,
+    /// The lines after the first, fields and notes alike, with their `\r` if they had one.
+    lines: Vec<String>,
+    /// Whether the contents ended with a newline.
+    trailing_newline: bool,
+}
+
+impl PasswordEntry {
+    /// Creates an entry that contains only `password`.
+    pub fn new(password: impl Into<String>) -> PasswordEntry {
+        PasswordEntry {
+            password: password.into(),
+            ..PasswordEntry::default()
+        }
+    }
+
+    /// Parses the decrypted `contents` of an entry.
+    ///
+    /// Lines are split on `\n` only, so a `\r` before it stays part of the line and is
+    /// written back by [`fmt::Display`].
+    pub fn parse(contents: &str) -> PasswordEntry {
+        let trailing_newline = contents.ends_with('\n');
+        let contents = contents.strip_suffix('\n').unwrap_or(contents);
+        let mut lines = contents.split('\n').map(str::to_string);
+        PasswordEntry {
+            password: lines.next().unwrap_or_default(),
+            lines: lines.collect(),
+            trailing_newline,
+        }
+    }
+
+    /// Returns the password: the first line.
+    pub fn password(&self) -> &str {
+        without_cr(&self.password)
+    }
+
+    /// Replaces the password, keeping the fields and notes.
+    pub fn set_password(&mut self, password: impl Into<String>) {
+        self.password = with_ending_of(&self.password, password.into());
+    }
+
+    /// Returns line `number` of the entry, counting from 1, which is the password.
+    pub fn line(&self, number: usize) -> Option<&str> {
+        match number {
+            0 => None,
+            1 => Some(self.password()),
+            n => self.lines.get(n - 2).map(|line| without_cr(line)),
+        }
+    }
+
+    /// Replaces line `number` of the entry, counting from 1, which is the password.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the line is replaced.
+    /// * `Err(StoreError::InvalidInput)` if the entry has no such line or `line` contains a
+    ///   newline.
+    pub fn set_line(&mut self, number: usize, line: impl Into<String>) -> Result<(), StoreError> {
+        let line = line.into();
+        if line.contains('\n') {
+            return Err(StoreError::InvalidInput(format!(
+                "'{}' is not a single line.",
+                line
+            )));
+        }
+        match number {
+            1 => self.password = with_ending_of(&self.password, line),
+            n if n >= 2 && n - 2 < self.lines.len() => {
+                self.lines[n - 2] = with_ending_of(&self.lines[n - 2], line)
+            }
+            n => {
+                return Err(StoreError::InvalidInput(format!(
+                    "The entry has no line {}.",
+                    n
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds `line` at the end of the entry, after the fields and notes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the line is added.
+    /// * `Err(StoreError::InvalidInput)` if `line` contains a newline.
+    pub fn push_line(&mut self, line: impl Into<String>) -> Result<(), StoreError> {
+        let line = line.into();
+        if line.contains('\n') {
+            return Err(StoreError::InvalidInput(format!(
+                "'{}' is not a single line.",
+                line
+            )));
+        }
+        let line = with_ending_of(&self.password, line);
+        self.lines.push(line);
+        Ok(())
+    }
+
+    /// Returns the value of the first field called `key`, ignoring case.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the `(key, value)` pairs of the fields, in order.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| parse_field(line))
+    }
+
+    /// Returns the lines after the password that are not fields, in order.
+    pub fn notes(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|line| parse_field(line).is_none())
+            .map(|line| without_cr(line))
+            .collect()
+    }
+
+    /// Sets the field `key` to `value`.
+    ///
+    /// The first field called `key` (ignoring case) is replaced. If there is none, the field
+    /// is added after the last field, or right after the password.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the field is set.
+    /// * `Err(StoreError::InvalidInput)` if `key` is empty, starts with whitespace or contains
+    ///   `:`, or either contains a newline, since the field could not be read back.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), StoreError> {
+        if key.is_empty()
+            || key.starts_with(char::is_whitespace)
+            || key.contains([':', '\n'])
+            || value.contains('\n')
+        {
+            return Err(StoreError::InvalidInput(format!(
+                "'{}: {}' is not a valid field.",
+                key, value
+            )));
+        }
+
+        let line = format!("{}: {}", key, value.trim());
+        let existing = self.lines.iter().position(|line| {
+            parse_field(line).is_some_and(|(name, _)| name.eq_ignore_ascii_case(key))
+        });
+        match existing {
+            Some(index) => self.lines[index] = with_ending_of(&self.lines[index], line),
+            None => {
+                let line = with_ending_of(&self.password, line);
+                let after_fields = self
+                    .lines
+                    .iter()
+                    .rposition(|line| parse_field(line).is_some())
+                    .map_or(0, |index| index + 1);
+                self.lines.insert(after_fields, line);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PasswordEntry {
+    /// Writes the contents of the entry, as stored.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.password)?;
+        for line in &self.lines {
+            write!(f, "\n{}", line)?;
+        }
+        if self.trailing_newline {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for PasswordEntry {
+    /// Serializes the entry as a map of its `password`, its `fields` as a map from key to
+    /// value, and its `notes` as a list of lines.
+    ///
+    /// Only the first field with each key (ignoring case) is included, the one that
+    /// [`PasswordEntry::field`] returns. The fields keep their order.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        for (key, value) in self.fields() {
+            if !fields
+                .iter()
+                .any(|(seen, _)| seen.eq_ignore_ascii_case(key))
+            {
+                fields.push((key, value));
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("password", self.password())?;
+        map.serialize_entry("fields", &FieldMap(&fields))?;
+        map.serialize_entry("notes", &self.notes())?;
+        map.end()
+    }
+}
+
+/// Serializes `(key, value)` pairs as a map, in order.
+struct FieldMap<'a>(&'a [(&'a str, &'a str)]);
+
+impl Serialize for FieldMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+/// Returns `line` without the `\r` of a `\r\n` line ending.
+fn without_cr(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Returns `line` with the same line ending as `old`, the line it replaces or follows, so that
+/// an entry with `\r\n` line endings keeps them.
+fn with_ending_of(old: &str, line: String) -> String {
+    if old.ends_with('\r') && !line.ends_with('\r') {
+        line + "\r"
+    } else {
+        line
+    }
+}
+
+/// Splits `line` into a field's key and value, or returns `None` if it is a note.
+fn parse_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = without_cr(line).split_once(':')?;
+    let is_field = !key.trim().is_empty()
+        && !key.starts_with(char::is_whitespace)
+        && (value.is_empty() || value.starts_with(char::is_whitespace));
+    is_field.then(|| (key.trim_end(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_gives_back_the_parsed_contents() {
+        for contents in [
+            "",
+            "\n",
+            "hunter2",
+            "hunter2\n",
+            "hunter2\nusername: alice\nurl: https://example.com\n",
+            "hunter2\n\nnotes after a blank line\n\n",
+            "hunter2\r\nusername: alice\r\nSecurity questions below\r\n",
+            "hunter2\r\nmixed: endings\nno newline at the end\r",
+            "  spaced password  \n  indented: note\n",
+        ] {
+            assert_eq!(PasswordEntry::parse(contents).to_string(), contents);
+        }
+    }
+
+    #[test]
+    fn crlf_endings_are_kept_but_not_returned() {
+        let mut entry = PasswordEntry::parse("hunter2\r\nusername: alice\r\nnote\r\n");
+        assert_eq!(entry.password(), "hunter2");
+        assert_eq!(entry.line(2), Some("username: alice"));
+        assert_eq!(entry.field("username"), Some("alice"));
+        assert_eq!(entry.notes(), ["note"]);
+
+        entry.set_password("correct horse");
+        entry.set_field("username", "bob").unwrap();
+        entry.set_field("url", "https://example.com").unwrap();
+        entry.set_line(4, "new note").unwrap();
+        entry.push_line("last").unwrap();
+        assert_eq!(
+            entry.to_string(),
+            "correct horse\r\nusername: bob\r\nurl: https://example.com\r\nnew note\r\nlast\r\n"
+        );
+    }
+
+    #[test]
+    fn fields_need_whitespace_after_the_colon() {
+        let entry = PasswordEntry::parse(concat!(
+            "hunter2\n",
+            "username: alice\n",
+            "url: https://example.com:8443/login\n",
+            "empty:\n",
+            "key:value\n",
+            "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP\n",
+            " indented: note\n",
+            ": no key\n",
+            "Username: shadowed\n",
+            "tab:\tseparated\n",
+        ));
+        assert_eq!(
+            entry.fields().collect::<Vec<_>>(),
+            [
+                ("username", "alice"),
+                ("url", "https://example.com:8443/login"),
+                ("empty", ""),
+                ("Username", "shadowed"),
+                ("tab", "separated"),
+            ]
+        );
+        assert_eq!(entry.field("USERNAME"), Some("alice"));
+        assert_eq!(entry.field("missing"), None);
+        assert_eq!(
+            entry.notes(),
+            [
+                "key:value",
+                "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP",
+                " indented: note",
+                ": no key",
+            ]
+        );
+    }
+
+    #[test]
+    fn entries_without_notes() {
+        assert!(PasswordEntry::parse("hunter2\n").notes().is_empty());
+        assert!(PasswordEntry::parse("hunter2\nusername: alice\n")
+            .notes()
+            .is_empty());
+        assert_eq!(PasswordEntry::parse("").password(), "");
+        assert_eq!(PasswordEntry::parse("").line(2), None);
+    }
+
+    #[test]
+    fn set_field_adds_new_fields_after_the_last_field() {
+        let mut entry = PasswordEntry::parse("hunter2\nnote\nusername: alice\nlast note");
+        entry.set_field("url", " https://example.com ").unwrap();
+        assert_eq!(
+            entry.to_string(),
+            "hunter2\nnote\nusername: alice\nurl: https://example.com\nlast note"
+        );
+
+        let mut entry = PasswordEntry::new("hunter2");
+        entry.set_field("username", "alice").unwrap();
+        assert_eq!(entry.to_string(), "hunter2\nusername: alice");
+
+        for (key, value) in [("", "a"), (" key", "a"), ("a:b", "c"), ("key", "a\nb")] {
+            assert!(entry.set_field(key, value).is_err(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn set_line_checks_the_line() {
+        let mut entry = PasswordEntry::parse("hunter2\nnote\n");
+        assert!(entry.set_line(0, "a").is_err());
+        assert!(entry.set_line(3, "a").is_err());
+        assert!(entry.set_line(2, "a\nb").is_err());
+        assert!(entry.push_line("a\nb").is_err());
+        entry.set_line(2, "changed").unwrap();
+        assert_eq!(entry.to_string(), "hunter2\nchanged\n");
+    }
+
+    #[test]
+    fn serializes_as_password_fields_and_notes() {
+        let entry = PasswordEntry::parse(
+            "hunter2\r\nUsername: alice\r\nurl: https://example.com\r\nusername: bob\r\nnote\r\n",
+        );
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"password":"hunter2","fields":{"Username":"alice","url":"https://example.com"},"notes":["note"]}"#
+        );
+        assert_eq!(
+            serde_yaml::to_string(&entry).unwrap(),
+            "password: hunter2\nfields:\n  Username: alice\n  url: https://example.com\nnotes:\n- note\n"
+        );
+
+        let entry = PasswordEntry::new("hunter2");
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"password":"hunter2","fields":{},"notes":[]}"#
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+/// The errors returned by the password store and its integrations.
+///
+/// Every variant maps to its own process exit code (see [`StoreError::exit_code`]), so
+/// scripts driving the command-line interface can tell, for example, a missing entry from
+/// a GPG failure.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The password store directory does not exist.
+    NotInitialized(PathBuf),
+    /// There is no entry or folder with this name in the store.
+    EntryNotFound(String),
+    /// The entry or folder already exists and may not be replaced.
+    EntryExists(String),
+    /// A folder was given where an entry was expected, e.g. `rm` without `--recursive`.
+    IsDirectory(String),
+    /// A name contains `..` segments that could escape the store.
+    SneakyPath(String),
+    /// A name resolves, e.g. through a symlinked folder, to a location outside the store.
+    OutsideStore(PathBuf),
+    /// No `.gpg-id` (or `.age-recipients`) governs a location, it lists no recipients, or a
+    /// recipient has no public key.
+    RecipientMissing(String),
+    /// The detached signature of a `.gpg-id` file does not exist.
+    SignatureMissing(PathBuf),
+    /// The detached signature of a `.gpg-id` file is not valid or not made by a trusted key.
+    SignatureInvalid(PathBuf),
+    /// GPG could not be run or exited with a non-success status.
+    GpgFailed {
+        /// What was being done, e.g. "GPG decryption failed for /path/to/entry.gpg".
+        context: String,
+        /// The exit code, or `None` if GPG could not be run or was killed by a signal.
+        status: Option<i32>,
+        /// What GPG wrote to standard error, without its status lines, or why it could not
+        /// be run.
+        stderr: String,
+        /// The cause of the failure, when GPG's status lines tell it.
+        problem: Option<GpgProblem>,
+    },
+    /// A crypto backend other than the `gpg` executable, such as the native OpenPGP or age
+    /// backend, failed, or entries could not be re-encrypted with any backend.
+    CryptoFailed {
+        /// The name of the backend, see [`crate::CryptoBackend::name`].
+        backend: &'static str,
+        /// What was being done, e.g. "age decryption failed for /path/to/entry.age".
+        context: String,
+        /// Why it failed, as the backend reported it.
+        message: String,
+    },
+    /// Git could not be run or exited with a non-success status.
+    GitFailed {
+        /// What was being done, e.g. "git commit failed".
+        context: String,
+        /// The exit code, or `None` if Git could not be run or was killed by a signal.
+        status: Option<i32>,
+        /// What Git wrote to standard error, or why it could not be run.
+        stderr: String,
+    },
+    /// Copying to or restoring the clipboard failed.
+    Clipboard(String),
+    /// An argument or the environment is invalid, e.g. a bad pattern or `$HOME` is unset.
+    InvalidInput(String),
+    /// A filesystem or I/O operation failed.
+    Io {
+        /// What was being done, e.g. "Error creating directory /path/to/store".
+        context: String,
+        /// The underlying error.
+        source: io::Error,
+    },
+}
+
+impl StoreError {
+    /// Creates a [`StoreError::Io`] from an I/O error and a description of what failed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::fs;
+    /// # use password_store_rs::StoreError;
+    /// # fn main() -> Result<(), StoreError> {
+    /// # let dir = std::env::temp_dir();
+    /// fs::create_dir_all(&dir)
+    ///     .map_err(|e| StoreError::io(format!("Error creating directory {}", dir.display()), e))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn io(context: impl Into<String>, source: io::Error) -> StoreError {
+        StoreError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Creates a [`StoreError::GpgFailed`] for a GPG process that exited with `status`.
+    pub fn gpg(context: impl Into<String>, status: ExitStatus, stderr: &[u8]) -> StoreError {
+        StoreError::GpgFailed {
+            context: context.into(),
+            status: status.code(),
+            stderr: String::from_utf8_lossy(stderr).trim().to_string(),
+            problem: None,
+        }
+    }
+
+    /// Creates a [`StoreError::CryptoFailed`] for a failure of the crypto backend called
+    /// `backend`, such as `age` or `openpgp`.
+    pub fn backend(
+        backend: &'static str,
+        context: impl Into<String>,
+        message: impl Into<String>,
+    ) -> StoreError {
+        StoreError::CryptoFailed {
+            backend,
+            context: context.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Creates a [`StoreError::GitFailed`] for a Git process that exited with `status`.
+    pub fn git(context: impl Into<String>, status: ExitStatus, stderr: &[u8]) -> StoreError {
+        StoreError::GitFailed {
+            context: context.into(),
+            status: status.code(),
+            stderr: String::from_utf8_lossy(stderr).trim().to_string(),
+        }
+    }
+
+    /// Returns the process exit code the command-line interface uses for this error.
+    ///
+    /// | Code | Error                                                  |
+    /// |------|--------------------------------------------------------|
+    /// | 1    | [`StoreError::Io`], `Clipboard` or `InvalidInput`      |
+    /// | 3    | [`StoreError::NotInitialized`]                         |
+    /// | 4    | [`StoreError::EntryNotFound`]                          |
+    /// | 5    | [`StoreError::EntryExists`]                            |
+    /// | 6    | [`StoreError::IsDirectory`]                            |
+    /// | 7    | [`StoreError::SneakyPath`] or `OutsideStore`           |
+    /// | 8    | [`StoreError::RecipientMissing`]                       |
+    /// | 9    | [`StoreError::SignatureMissing`] or `SignatureInvalid` |
+    /// | 10   | [`StoreError::GpgFailed`] or `CryptoFailed`            |
+    /// | 11   | [`StoreError::GitFailed`]                              |
+    ///
+    /// Code 2 is left to `clap` for usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StoreError::Io { .. } | StoreError::Clipboard(_) | StoreError::InvalidInput(_) => 1,
+            StoreError::NotInitialized(_) => 3,
+            StoreError::EntryNotFound(_) => 4,
+            StoreError::EntryExists(_) => 5,
+            StoreError::IsDirectory(_) => 6,
+            StoreError::SneakyPath(_) | StoreError::OutsideStore(_) => 7,
+            StoreError::RecipientMissing(_) => 8,
+            StoreError::SignatureMissing(_) | StoreError::SignatureInvalid(_) => 9,
+            StoreError::GpgFailed { .. } | StoreError::CryptoFailed { .. } => 10,
+            StoreError::GitFailed { .. } => 11,
+        }
+    }
+
+    /// Returns a stable, machine-readable name for the kind of error, such as
+    /// `"entry_not_found"`: the variant name in snake case.
+    ///
+    /// The command-line interface reports it as `error` in `--format json` and `yaml` output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StoreError::NotInitialized(_) => "not_initialized",
+            StoreError::EntryNotFound(_) => "entry_not_found",
+            StoreError::EntryExists(_) => "entry_exists",
+            StoreError::IsDirectory(_) => "is_directory",
+            StoreError::SneakyPath(_) => "sneaky_path",
+            StoreError::OutsideStore(_) => "outside_store",
+            StoreError::RecipientMissing(_) => "recipient_missing",
+            StoreError::SignatureMissing(_) => "signature_missing",
+            StoreError::SignatureInvalid(_) => "signature_invalid",
+            StoreError::GpgFailed { .. } => "gpg_failed",
+            StoreError::CryptoFailed { .. } => "crypto_failed",
+            StoreError::GitFailed { .. } => "git_failed",
+            StoreError::Clipboard(_) => "clipboard",
+            StoreError::InvalidInput(_) => "invalid_input",
+            StoreError::Io { .. } => "io",
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotInitialized(path) => write!(
+                f,
+                "Password store '{}' does not exist. Try \"pass init\".",
+                path.display()
+            ),
+            StoreError::EntryNotFound(name) => write!(f, "{} is not in the password store.", name),
+            StoreError::EntryExists(name) => write!(f, "{} already exists.", name),
+            StoreError::IsDirectory(name) => {
+                write!(f, "{} is a directory. Use --recursive to remove it.", name)
+            }
+            StoreError::SneakyPath(_) => write!(
+                f,
+                "You've attempted to pass a sneaky path to pass. Go home."
+            ),
+            StoreError::OutsideStore(path) => {
+                write!(f, "{} is outside of the password store.", path.display())
+            }
+            StoreError::RecipientMissing(message)
+            | StoreError::Clipboard(message)
+            | StoreError::InvalidInput(message) => write!(f, "{}", message),
+            StoreError::SignatureMissing(path) => {
+                write!(f, "Signature for {} does not exist.", path.display())
+            }
+            StoreError::SignatureInvalid(path) => {
+                write!(f, "Signature for {} is invalid.", path.display())
+            }
+            StoreError::GpgFailed {
+                context,
+                status,
+                problem: Some(problem),
+                ..
+            } => {
+                write!(f, "{}", context)?;
+                if let Some(code) = status {
+                    write!(f, " (exit status {})", code)?;
+                }
+                write!(f, ": {}", problem)
+            }
+            StoreError::GpgFailed {
+                context,
+                status,
+                stderr,
+                problem: None,
+            }
+            | StoreError::GitFailed {
+                context,
+                status,
+                stderr,
+            } => {
+                write!(f, "{}", context)?;
+                if let Some(code) = status {
+                    write!(f, " (exit status {})", code)?;
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+            StoreError::CryptoFailed {
+                context, message, ..
+            } => {
+                write!(f, "{}", context)?;
+                if !message.is_empty() {
+                    write!(f, ": {}", message)?;
+                }
+                Ok(())
+            }
+            StoreError::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A known cause of a GPG failure, read from the status lines GPG writes with `--status-fd`.
+///
+/// Its [`fmt::Display`] form says what went wrong and how to fix it, and is shown in place of
+/// GPG's own messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpgProblem {
+    /// The file is encrypted only for these key IDs, and none of them is in the secret
+    /// keyring (`NO_SECKEY`).
+    NoSecretKey(Vec<String>),
+    /// pinentry was started, but no passphrase unlocked the secret key
+    /// (`PINENTRY_LAUNCHED`), e.g. because the prompt was cancelled or could not be shown.
+    PassphraseNotEntered,
+    /// The file could not be decrypted for another reason (`DECRYPTION_FAILED`).
+    DecryptionFailed,
+    /// The file does not contain OpenPGP data (`NODATA`).
+    NoData,
+    /// A key has expired (`KEYEXPIRED`). Holds its fingerprint when GPG names it.
+    KeyExpired(Option<String>),
+    /// A recipient cannot be encrypted for (`INV_RECP`).
+    InvalidRecipient {
+        /// The recipient as it was given, e.g. a line of `.gpg-id`.
+        recipient: String,
+        /// GPG's reason code, e.g. 4 for a revoked key or 10 for an untrusted one.
+        reason: u32,
+    },
+}
+
+impl fmt::Display for GpgProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpgProblem::NoSecretKey(keys) if keys.len() == 1 => write!(
+                f,
+                "the entry is encrypted for key {}, which is not in your secret keyring",
+                keys[0]
+            ),
+            GpgProblem::NoSecretKey(keys) => write!(
+                f,
+                "the entry is encrypted for keys {}, none of which are in your secret keyring",
+                keys.join(", ")
+            ),
+            GpgProblem::PassphraseNotEntered => write!(
+                f,
+                "no passphrase was entered to unlock the secret key. If no prompt appeared, \
+                 set GPG_TTY=$(tty) and try again"
+            ),
+            GpgProblem::DecryptionFailed => {
+                write!(f, "the file could not be decrypted and may be damaged")
+            }
+            GpgProblem::NoData => write!(f, "the file does not contain OpenPGP data"),
+            GpgProblem::KeyExpired(key) => {
+                match key {
+                    Some(key) => write!(f, "key {} has expired", key)?,
+                    None => write!(f, "a key has expired")?,
+                }
+                write!(
+                    f,
+                    ". Extend it with \"gpg --quick-set-expire\" or replace it in .gpg-id"
+                )
+            }
+            GpgProblem::InvalidRecipient { recipient, reason } => {
+                write!(f, "recipient '{}' cannot be used: ", recipient)?;
+                match reason {
+                    0 | 1 => write!(f, "there is no public key for it. Import it or fix .gpg-id"),
+                    2 => write!(f, "it matches several keys. Use a fingerprint in .gpg-id"),
+                    3 => write!(f, "the key cannot encrypt"),
+                    4 => write!(f, "the key is revoked"),
+                    5 => write!(f, "the key has expired"),
+                    10 => write!(
+                        f,
+                        "the key is not trusted. Certify it with \"gpg --lsign-key\" or set \
+                         PASSWORD_STORE_GPG_OPTS=\"--trust-model always\""
+                    ),
+                    13 => write!(f, "the key is disabled"),
+                    code => write!(f, "GPG rejected it (reason {})", code),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One error of every variant, with the exit code that the table in the docs of
+    /// [`StoreError::exit_code`] gives it.
+    fn every_variant() -> Vec<(StoreError, i32)> {
+        vec![
+            (StoreError::NotInitialized(PathBuf::from("/store")), 3),
+            (StoreError::EntryNotFound("a".to_string()), 4),
+            (StoreError::EntryExists("a".to_string()), 5),
+            (StoreError::IsDirectory("a".to_string()), 6),
+            (StoreError::SneakyPath("../a".to_string()), 7),
+            (StoreError::OutsideStore(PathBuf::from("/tmp")), 7),
+            (StoreError::RecipientMissing("a".to_string()), 8),
+            (StoreError::SignatureMissing(PathBuf::from(".gpg-id")), 9),
+            (StoreError::SignatureInvalid(PathBuf::from(".gpg-id")), 9),
+            (
+                StoreError::GpgFailed {
+                    context: "gpg --decrypt".to_string(),
+                    status: Some(2),
+                    stderr: String::new(),
+                    problem: None,
+                },
+                10,
+            ),
+            (StoreError::backend("age", "Failed to decrypt", ""), 10),
+            (
+                StoreError::GitFailed {
+                    context: "git commit".to_string(),
+                    status: Some(1),
+                    stderr: String::new(),
+                },
+                11,
+            ),
+            (StoreError::Clipboard("no clipboard".to_string()), 1),
+            (StoreError::InvalidInput("bad".to_string()), 1),
+            (StoreError::io("read", io::Error::other("failed")), 1),
+        ]
+    }
+
+    #[test]
+    fn exit_codes_match_the_documented_table() {
+        for (error, code) in every_variant() {
+            assert_eq!(error.exit_code(), code, "exit code of {:?}", error);
+        }
+    }
+
+    #[test]
+    fn exit_code_2_is_left_to_clap() {
+        assert!(every_variant()
+            .iter()
+            .all(|(error, _)| error.exit_code() != 2));
+    }
+}
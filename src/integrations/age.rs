@@ -0,0 +1,242 @@
+use crate::crypto::CryptoBackend;
+use crate::error::StoreError;
+use age::x25519;
+use age::{Decryptor, Encryptor};
+use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The file that lists the age recipients of a folder, in place of `.gpg-id`.
+pub const AGE_RECIPIENTS_FILE: &str = ".age-recipients";
+
+/// A [`CryptoBackend`] that encrypts entries with [age](https://age-encryption.org) instead
+/// of OpenPGP.
+///
+/// A store whose root contains a `.age-recipients` file instead of a `.gpg-id` uses this
+/// backend (see [`crate::PasswordStore::open`]). Its entries are stored as `.age` files, and
+/// every folder's recipients are read from the nearest `.age-recipients`, which lists one
+/// `age1...` public key per line, with `#` comments, just like a `.gpg-id`.
+///
+/// Entries are decrypted with the identities in the files listed in
+/// `PASSWORD_STORE_AGE_IDENTITIES` (separated like `$PATH`), as written by `age-keygen`.
+/// The files are only read when an entry is decrypted, so listing or adding entries does
+/// not need them.
+///
+/// age has no signatures, so `.age-recipients` files cannot be signed, and keys cannot be
+/// generated by the store.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::integrations::age::AgeBackend;
+/// # use password_store_rs::PasswordStore;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// // PASSWORD_STORE_AGE_IDENTITIES=/home/me/.config/age/key.txt
+/// let backend = AgeBackend::from_env();
+/// let store = PasswordStore::with_backend("/home/me/.password-store", Box::new(backend))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct AgeBackend {
+    /// The identity files that entries are decrypted with.
+    identity_files: Vec<PathBuf>,
+}
+
+impl AgeBackend {
+    /// Creates a backend that decrypts with the identity files listed in
+    /// `PASSWORD_STORE_AGE_IDENTITIES`.
+    pub fn from_env() -> AgeBackend {
+        let identity_files = env::var_os("PASSWORD_STORE_AGE_IDENTITIES")
+            .map(|value| env::split_paths(&value).collect())
+            .unwrap_or_default();
+        AgeBackend::with_identity_files(identity_files)
+    }
+
+    /// Creates a backend that decrypts with the identities in `identity_files`.
+    pub fn with_identity_files(identity_files: Vec<PathBuf>) -> AgeBackend {
+        AgeBackend { identity_files }
+    }
+
+    /// Reads the identities from every identity file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<x25519::Identity>)` with the identities in file order.
+    /// * `Err(StoreError::InvalidInput)` if no identity file is configured.
+    /// * `Err(StoreError::Io)` if a file cannot be read.
+    /// * `Err(StoreError::CryptoFailed)` if a file contains anything but identities and comments.
+    fn identities(&self) -> Result<Vec<x25519::Identity>, StoreError> {
+        let files: Vec<&PathBuf> = self
+            .identity_files
+            .iter()
+            .filter(|file| !file.as_os_str().is_empty())
+            .collect();
+        if files.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "PASSWORD_STORE_AGE_IDENTITIES must name the age identity files to decrypt \
+                 with, e.g. from \"age-keygen -o key.txt\"."
+                    .to_string(),
+            ));
+        }
+
+        let mut identities = Vec::new();
+        for file in files {
+            let contents = fs::read_to_string(file)
+                .map_err(|e| StoreError::io(format!("Failed to read {}", file.display()), e))?;
+            for (number, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                // Only the line number is reported, so a secret key never ends up in an error.
+                let identity = line.parse::<x25519::Identity>().map_err(|_| {
+                    age_failed(
+                        &format!("Failed to read age identities from {}", file.display()),
+                        format!("line {} is not an age identity", number + 1),
+                    )
+                })?;
+                identities.push(identity);
+            }
+        }
+        Ok(identities)
+    }
+}
+
+impl CryptoBackend for AgeBackend {
+    /// Encrypts `contents` to every age recipient and writes the binary age file to
+    /// `output`, replacing any existing file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the file was written successfully.
+    /// * `Err(StoreError::RecipientMissing)` if a recipient is not an age public key.
+    /// * `Err(StoreError::CryptoFailed)` if encryption fails.
+    /// * `Err(StoreError::Io)` if the file cannot be written.
+    fn encrypt(
+        &self,
+        contents: &[u8],
+        recipients: &[String],
+        output: &Path,
+    ) -> Result<(), StoreError> {
+        let recipients = recipients
+            .iter()
+            .map(|recipient| parse_recipient(recipient))
+            .collect::<Result<Vec<_>, _>>()?;
+        let context = format!("age encryption failed for {}", output.display());
+
+        let encryptor = Encryptor::with_recipients(
+            recipients
+                .iter()
+                .map(|recipient| recipient as &dyn age::Recipient),
+        )
+        .map_err(|e| age_failed(&context, e))?;
+
+        let mut encrypted = Vec::new();
+        let mut writer = encryptor
+            .wrap_output(&mut encrypted)
+            .map_err(|e| age_failed(&context, e))?;
+        writer
+            .write_all(contents)
+            .and_then(|_| writer.finish())
+            .map_err(|e| age_failed(&context, e))?;
+
+        fs::write(output, encrypted)
+            .map_err(|e| StoreError::io(format!("Failed to write {}", output.display()), e))
+    }
+
+    /// Decrypts the age file at `path` with the identities from `PASSWORD_STORE_AGE_IDENTITIES`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` with the decrypted bytes.
+    /// * `Err(StoreError::InvalidInput)` if no identity file is configured.
+    /// * `Err(StoreError::CryptoFailed)` if no identity can decrypt the file.
+    /// * `Err(StoreError::Io)` if a file cannot be read.
+    fn decrypt(&self, path: &Path) -> Result<Vec<u8>, StoreError> {
+        let ciphertext = fs::read(path)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+        let identities = self.identities()?;
+        let context = format!("age decryption failed for {}", path.display());
+
+        let decryptor =
+            Decryptor::new_buffered(&ciphertext[..]).map_err(|e| age_failed(&context, e))?;
+        let mut reader = decryptor
+            .decrypt(
+                identities
+                    .iter()
+                    .map(|identity| identity as &dyn age::Identity),
+            )
+            .map_err(|e| age_failed(&context, e))?;
+
+        let mut plaintext = Vec::new();
+        reader
+            .read_to_end(&mut plaintext)
+            .map_err(|e| age_failed(&context, e))?;
+        Ok(plaintext)
+    }
+
+    /// Always fails, since age has no signatures.
+    fn sign(&self, _path: &Path, _signing_keys: &[String]) -> Result<(), StoreError> {
+        Err(no_signatures())
+    }
+
+    /// Always fails, since age has no signatures.
+    fn verify(&self, _path: &Path, _signature: &Path) -> Result<HashSet<String>, StoreError> {
+        Err(no_signatures())
+    }
+
+    /// Returns the public keys (`age1...`) of the identities in the identity files.
+    fn list_keys(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self
+            .identities()?
+            .iter()
+            .map(|identity| identity.to_public().to_string())
+            .collect())
+    }
+
+    /// Returns whether `key_id` is a valid age public key.
+    ///
+    /// Any age recipient can be encrypted to, so there is no keyring to look it up in.
+    fn key_exists(&self, key_id: &str) -> Result<bool, StoreError> {
+        Ok(key_id.trim().parse::<x25519::Recipient>().is_ok())
+    }
+
+    /// Returns `age`.
+    fn name(&self) -> &'static str {
+        "age"
+    }
+
+    /// Returns `age`.
+    fn extension(&self) -> &'static str {
+        "age"
+    }
+
+    /// Returns `.age-recipients`.
+    fn recipients_file(&self) -> &'static str {
+        AGE_RECIPIENTS_FILE
+    }
+}
+
+/// Parses a line of a `.age-recipients` file as an age public key.
+fn parse_recipient(recipient: &str) -> Result<x25519::Recipient, StoreError> {
+    recipient.trim().parse().map_err(|_| {
+        StoreError::RecipientMissing(format!("'{}' is not a valid age recipient.", recipient))
+    })
+}
+
+/// Returns the error for an operation that needs signatures.
+fn no_signatures() -> StoreError {
+    StoreError::InvalidInput(format!(
+        "age cannot sign or verify {} files. Unset PASSWORD_STORE_SIGNING_KEY for age stores.",
+        AGE_RECIPIENTS_FILE
+    ))
+}
+
+/// Returns the error for a failed age operation.
+fn age_failed(context: &str, e: impl fmt::Display) -> StoreError {
+    StoreError::backend("age", context, e.to_string())
+}
@@ -0,0 +1,419 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::StoreError;
+use crate::utils::executable_in_path;
+
+/// Number of seconds a secret stays on the clipboard when `PASSWORD_STORE_CLIP_TIME` is unset.
+const DEFAULT_CLIP_TIME: u64 = 45;
+
+/// The clipboard implementations a secret can be copied to.
+///
+/// The backend is chosen by [`Clipboard::detect`], either from the
+/// `PASSWORD_STORE_CLIP_BACKEND` environment variable or by probing the
+/// running display server. The `File` backend stores the clipboard contents
+/// in a plain file and exists so the clipboard flow can be exercised without
+/// a graphical session.
+pub enum Clipboard {
+    /// Wayland clipboard via `wl-copy` / `wl-paste`.
+    Wayland,
+    /// X11 clipboard via `xclip`.
+    Xclip,
+    /// X11 clipboard via `xsel`.
+    Xsel,
+    /// A file standing in for the clipboard, taken from `PASSWORD_STORE_CLIP_FILE`.
+    File(PathBuf),
+}
+
+impl Clipboard {
+    /// Selects the clipboard backend to use.
+    ///
+    /// If `PASSWORD_STORE_CLIP_BACKEND` is set to one of `wl-copy`, `xclip`,
+    /// `xsel` or `file`, that backend is used. Otherwise `wl-copy` is preferred
+    /// when `WAYLAND_DISPLAY` is set, followed by `xclip` and then `xsel` when
+    /// `DISPLAY` is set.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Clipboard)` with the selected backend.
+    /// * `Err(StoreError::Clipboard)` if the requested backend is unknown or no usable clipboard was found.
+    pub fn detect() -> Result<Clipboard, StoreError> {
+        if let Ok(backend) = env::var("PASSWORD_STORE_CLIP_BACKEND") {
+            return match backend.as_str() {
+                "wl-copy" => Ok(Clipboard::Wayland),
+                "xclip" => Ok(Clipboard::Xclip),
+                "xsel" => Ok(Clipboard::Xsel),
+                "file" => env::var("PASSWORD_STORE_CLIP_FILE")
+                    .map(|path| Clipboard::File(PathBuf::from(path)))
+                    .map_err(|_| {
+                        StoreError::Clipboard(
+                            "PASSWORD_STORE_CLIP_FILE must be set to use the file clipboard backend."
+                                .to_string(),
+                        )
+                    }),
+                other => Err(StoreError::Clipboard(format!(
+                    "Unknown clipboard backend '{}'.",
+                    other
+                ))),
+            };
+        }
+
+        if env::var_os("WAYLAND_DISPLAY").is_some() && executable_in_path("wl-copy") {
+            return Ok(Clipboard::Wayland);
+        }
+        if env::var_os("DISPLAY").is_some() {
+            if executable_in_path("xclip") {
+                return Ok(Clipboard::Xclip);
+            }
+            if executable_in_path("xsel") {
+                return Ok(Clipboard::Xsel);
+            }
+        }
+
+        Err(StoreError::Clipboard(
+            "No X11 or Wayland display detected to copy to clipboard.".to_string(),
+        ))
+    }
+
+    /// Replaces the clipboard contents with `contents`.
+    ///
+    /// Copying an empty slice clears the clipboard.
+    pub fn copy(&self, contents: &[u8]) -> Result<(), StoreError> {
+        let selection = selection();
+        let mut cmd = match self {
+            Clipboard::File(path) => {
+                return fs::write(path, contents).map_err(|e| {
+                    StoreError::io(
+                        format!("Failed to write clipboard file {}", path.display()),
+                        e,
+                    )
+                });
+            }
+            Clipboard::Wayland => {
+                let mut cmd = Command::new("wl-copy");
+                if selection == "primary" {
+                    cmd.arg("--primary");
+                }
+                if contents.is_empty() {
+                    cmd.arg("--clear");
+                }
+                cmd
+            }
+            Clipboard::Xclip => {
+                let mut cmd = Command::new("xclip");
+                cmd.args(["-selection", &selection]);
+                cmd
+            }
+            Clipboard::Xsel => {
+                let mut cmd = Command::new("xsel");
+                cmd.arg(format!("--{}", selection)).arg("--input");
+                cmd
+            }
+        };
+
+        // wl-copy and xclip fork a process that keeps serving the selection, so
+        // their output must not be tied to ours.
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| StoreError::io("Failed to execute clipboard command", e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(contents)
+                .map_err(|e| StoreError::io("Failed to write to clipboard command", e))?;
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| StoreError::io("Failed to wait on clipboard command", e))?;
+        if !status.success() {
+            return Err(StoreError::Clipboard(format!(
+                "Clipboard command failed with status: {}",
+                status
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the current clipboard contents.
+    ///
+    /// An empty or missing clipboard yields an empty vector rather than an error.
+    pub fn paste(&self) -> Result<Vec<u8>, StoreError> {
+        let selection = selection();
+        let mut cmd = match self {
+            Clipboard::File(path) => {
+                return match fs::read(path) {
+                    Ok(contents) => Ok(contents),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+                    Err(e) => Err(StoreError::io(
+                        format!("Failed to read clipboard file {}", path.display()),
+                        e,
+                    )),
+                };
+            }
+            Clipboard::Wayland => {
+                let mut cmd = Command::new("wl-paste");
+                cmd.arg("--no-newline");
+                if selection == "primary" {
+                    cmd.arg("--primary");
+                }
+                cmd
+            }
+            Clipboard::Xclip => {
+                let mut cmd = Command::new("xclip");
+                cmd.args(["-o", "-selection", &selection]);
+                cmd
+            }
+            Clipboard::Xsel => {
+                let mut cmd = Command::new("xsel");
+                cmd.arg(format!("--{}", selection)).arg("--output");
+                cmd
+            }
+        };
+
+        let output = cmd
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| StoreError::io("Failed to execute clipboard command", e))?;
+
+        // Paste commands exit non-zero when the selection is empty.
+        if !output.status.success() {
+            return Ok(Vec::new());
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+/// Returns the X selection to use, from `PASSWORD_STORE_X_SELECTION` (default `clipboard`).
+fn selection() -> String {
+    env::var("PASSWORD_STORE_X_SELECTION").unwrap_or_else(|_| "clipboard".to_string())
+}
+
+/// Returns how long a secret stays on the clipboard, from `PASSWORD_STORE_CLIP_TIME` (default 45).
+pub fn clip_time() -> Result<u64, StoreError> {
+    match env::var("PASSWORD_STORE_CLIP_TIME") {
+        Ok(value) => value.trim().parse().map_err(|_| {
+            StoreError::InvalidInput(format!("Invalid PASSWORD_STORE_CLIP_TIME '{}'.", value))
+        }),
+        Err(_) => Ok(DEFAULT_CLIP_TIME),
+    }
+}
+
+/// Copies `secret` to the clipboard and schedules its removal.
+///
+/// The current clipboard contents are saved before the secret is copied. `clearer` is then
+/// started detached, with the number of seconds appended as its last argument, and handed
+/// the secret and the saved contents on stdin. It must run [`restore_after_timeout`] with
+/// those seconds, e.g. through a hidden subcommand of the calling program. After the timeout
+/// it restores the saved contents, but only if the clipboard still holds the secret.
+///
+/// # Arguments
+///
+/// * `secret` - The bytes to place on the clipboard.
+/// * `clearer` - The command that runs [`restore_after_timeout`] in a new process.
+///
+/// # Returns
+///
+/// * `Ok(u64)` with the number of seconds until the clipboard is cleared.
+/// * `Err(StoreError)` if no clipboard is available or any command fails.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::process::Command;
+/// # use password_store_rs::integrations::clipboard::copy_with_timeout;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// // `my-app clear-clipboard <seconds>` calls `restore_after_timeout(seconds)`.
+/// let mut clearer = Command::new("my-app");
+/// clearer.arg("clear-clipboard");
+/// let seconds = copy_with_timeout(b"hunter2", clearer)?;
+/// println!("Copied to clipboard. Will clear in {} seconds.", seconds);
+/// # Ok(())
+/// # }
+/// ```
+pub fn copy_with_timeout(secret: &[u8], mut clearer: Command) -> Result<u64, StoreError> {
+    let seconds = clip_time()?;
+    let clipboard = Clipboard::detect()?;
+
+    let previous = clipboard.paste().unwrap_or_default();
+    clipboard.copy(secret)?;
+
+    // The clearer runs in its own process group so that it survives the parent
+    // exiting and is not interrupted by Ctrl+C in the terminal.
+    #[allow(clippy::zombie_processes)]
+    let mut child = clearer
+        .arg(seconds.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(|e| StoreError::io("Failed to start clipboard clearer", e))?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| {
+        StoreError::Clipboard("Failed to open clipboard clearer stdin".to_string())
+    })?;
+    writeln!(stdin, "{}", STANDARD.encode(secret))
+        .and_then(|_| writeln!(stdin, "{}", STANDARD.encode(&previous)))
+        .map_err(|e| StoreError::io("Failed to hand off to clipboard clearer", e))?;
+
+    Ok(seconds)
+}
+
+/// Body of the background clearer started by [`copy_with_timeout`].
+///
+/// Reads the base64-encoded secret and previous clipboard contents from stdin,
+/// waits `seconds`, and restores the previous contents if the clipboard still
+/// holds the secret (see [`restore_if_unchanged`]).
+pub fn restore_after_timeout(seconds: u64) -> Result<(), StoreError> {
+    let (secret, previous) = read_clipboard_state(io::stdin().lock())?;
+
+    thread::sleep(Duration::from_secs(seconds));
+
+    restore_if_unchanged(&Clipboard::detect()?, &secret, &previous)?;
+    Ok(())
+}
+
+/// Puts `previous` back on `clipboard` if it still holds `secret`. If something else has
+/// been copied in the meantime the clipboard is left alone.
+///
+/// # Returns
+///
+/// * `Ok(true)` if `previous` was restored, `Ok(false)` if the clipboard was left alone.
+/// * `Err(StoreError)` if the clipboard cannot be read or written.
+pub fn restore_if_unchanged(
+    clipboard: &Clipboard,
+    secret: &[u8],
+    previous: &[u8],
+) -> Result<bool, StoreError> {
+    if clipboard.paste()? != secret {
+        return Ok(false);
+    }
+    clipboard.copy(previous)?;
+    Ok(true)
+}
+
+/// Reads the secret and the previous clipboard contents that [`copy_with_timeout`] hands
+/// to the clearer: one base64-encoded line each.
+fn read_clipboard_state(input: impl Read) -> Result<(Vec<u8>, Vec<u8>), StoreError> {
+    let mut lines = BufReader::new(input).lines();
+    let mut next_decoded = || -> Result<Vec<u8>, StoreError> {
+        let line = lines
+            .next()
+            .unwrap_or_else(|| Ok(String::new()))
+            .map_err(|e| StoreError::io("Failed to read clipboard state", e))?;
+        STANDARD
+            .decode(line.trim())
+            .map_err(|e| StoreError::Clipboard(format!("Invalid clipboard state: {}", e)))
+    };
+    let secret = next_decoded()?;
+    let previous = next_decoded()?;
+    Ok((secret, previous))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Returns a path in the temporary directory for the test `name`, with no file at it.
+    fn temp_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "password-store-rs-clip-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn the_file_backend_copies_and_pastes() {
+        let path = temp_file("copy");
+        let clipboard = Clipboard::File(path.clone());
+        assert_eq!(clipboard.paste().unwrap(), b"");
+
+        clipboard.copy(b"hunter2").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"hunter2");
+        assert_eq!(clipboard.paste().unwrap(), b"hunter2");
+
+        clipboard.copy(b"").unwrap();
+        assert_eq!(clipboard.paste().unwrap(), b"");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn the_previous_contents_are_restored_if_unchanged() {
+        let path = temp_file("unchanged");
+        let clipboard = Clipboard::File(path.clone());
+        clipboard.copy(b"hunter2").unwrap();
+
+        assert!(restore_if_unchanged(&clipboard, b"hunter2", b"previous").unwrap());
+        assert_eq!(clipboard.paste().unwrap(), b"previous");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_changed_clipboard_is_left_alone() {
+        let path = temp_file("changed");
+        let clipboard = Clipboard::File(path.clone());
+        clipboard.copy(b"copied since").unwrap();
+
+        assert!(!restore_if_unchanged(&clipboard, b"hunter2", b"previous").unwrap());
+        assert_eq!(clipboard.paste().unwrap(), b"copied since");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn copy_with_timeout_hands_the_secret_to_the_clearer() {
+        let clip_file = temp_file("timeout");
+        let state = temp_file("timeout-state");
+        fs::write(&clip_file, "previous").unwrap();
+        env::set_var("PASSWORD_STORE_CLIP_BACKEND", "file");
+        env::set_var("PASSWORD_STORE_CLIP_FILE", &clip_file);
+        env::set_var("PASSWORD_STORE_CLIP_TIME", "7");
+
+        // Stands in for the clearer: saves its last argument and stdin to `state`.
+        let mut clearer = Command::new("sh");
+        clearer.args([
+            "-c",
+            "{ echo \"$1\"; cat; } > \"$0.tmp\" && mv \"$0.tmp\" \"$0\"",
+        ]);
+        clearer.arg(&state);
+        assert_eq!(copy_with_timeout(b"hunter2", clearer).unwrap(), 7);
+        let clipboard = Clipboard::detect().unwrap();
+        assert_eq!(clipboard.paste().unwrap(), b"hunter2");
+
+        let started = Instant::now();
+        while !state.exists() && started.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(20));
+        }
+        let contents = fs::read_to_string(&state).unwrap();
+        let (seconds, handed_off) = contents.split_once('\n').unwrap();
+        assert_eq!(seconds, "7");
+        let (secret, previous) = read_clipboard_state(handed_off.as_bytes()).unwrap();
+        assert_eq!(secret, b"hunter2");
+        assert_eq!(previous, b"previous");
+
+        assert!(restore_if_unchanged(&clipboard, &secret, &previous).unwrap());
+        assert_eq!(fs::read(&clip_file).unwrap(), b"previous");
+
+        env::remove_var("PASSWORD_STORE_CLIP_BACKEND");
+        env::remove_var("PASSWORD_STORE_CLIP_FILE");
+        env::remove_var("PASSWORD_STORE_CLIP_TIME");
+        fs::remove_file(&clip_file).unwrap();
+        fs::remove_file(&state).unwrap();
+    }
+}
@@ -1,11 +1,29 @@
+use crate::error::StoreError;
+use crate::journal::JOURNAL_DIR;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+
+/// Returns whether `dir` is inside a Git working tree.
+///
+/// This runs `git rev-parse --show-toplevel` in `dir` and reports whether it succeeded.
+/// A missing `git` executable or a nonexistent directory count as "not a repository".
+pub fn is_git_repo(dir: &Path) -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
 
 /// Adds a file to Git and commits the change with the provided commit message.
 ///
 /// This function first checks if the file is within a Git repository by attempting to
 /// retrieve the repository top-level directory from the file's parent. If the file is
-/// not inside a Git repository, the function logs a warning and returns `Ok(())`.
+/// not inside a Git repository, or it is unchanged since the last commit, nothing is
+/// committed. Nothing is printed either way; the caller decides what to tell the user.
 ///
 /// # Arguments
 ///
@@ -14,114 +32,239 @@ use std::process::Command;
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the file is added (and committed) successfully, or if the file is not in a Git repository.
-/// * `Err(String)` if there is an error executing either the `git add` or `git commit` command.
-pub fn git_add_file(file_path: &str, message: &str) -> Result<(), String> {
+/// * `Ok(true)` if the file is added and committed.
+/// * `Ok(false)` if there was nothing to commit, or the file is not in a Git repository.
+/// * `Err(StoreError::GitFailed)` if `git add` or `git commit` fails.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::integrations::git::git_add_file;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// if !git_add_file("/path/to/store/.gpg-id", "Set GPG id to alice@example.com.")? {
+///     println!("Nothing to commit.");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn git_add_file(file_path: &str, message: &str) -> Result<bool, StoreError> {
     // Determine the parent directory of the file.
-    let file_parent = Path::new(file_path)
-        .parent()
-        .ok_or_else(|| format!("Could not determine parent directory of {}", file_path))?;
-
-    // Check if the parent directory is inside a Git repository.
-    let repo_toplevel = Command::new("git")
-        .args(&["rev-parse", "--show-toplevel"])
-        .current_dir(file_parent)
-        .output();
+    let file_parent = parent_dir(file_path)?;
 
-    // If the file is not inside a Git repository, return Ok(()) silently.
-    if repo_toplevel.is_err() || !repo_toplevel.unwrap().status.success() {
-        return Ok(());
+    // If the file is not inside a Git repository, there is nothing to commit.
+    if !is_git_repo(file_parent) {
+        return Ok(false);
     }
 
     // Run "git add <file_path>"
-    let add_status = match Command::new("git")
-        .args(&["add", file_path])
-        .status()
-        .map_err(|e| format!("Failed to execute git add: {}", e))
-    {
-        Ok(status) => status,
-        Err(err) => return Err(err),
-    };
-
-    if !add_status.success() {
-        return Err(format!(
-            "git add command failed with status: {}",
-            add_status
-        ));
-    }
+    run_git(file_parent, &["add", "--", file_path])?;
 
-    // Run "git commit -m <message> <file_path>"
-    let commit_status = match Command::new("git")
-        .args(&["commit", "-m", message, file_path])
-        .status()
-        .map_err(|e| format!("Failed to execute git commit: {}", e))
-    {
-        Ok(status) => status,
-        Err(err) => return Err(err),
-    };
-
-    if !commit_status.success() {
-        // It's common that there's nothing new to commit. We warn and continue.
-        println!("Warning: git commit returned non-zero (possibly nothing to commit).");
+    // Skip the commit if the file is unchanged.
+    let staged = git_output(
+        file_parent,
+        &["diff", "--cached", "--quiet", "--", file_path],
+    )?;
+    if staged.status.success() {
+        return Ok(false);
     }
 
-    Ok(())
+    // Run "git commit -m <message> <file_path>"
+    run_git(
+        file_parent,
+        &["commit", "-q", "-m", message, "--", file_path],
+    )?;
+    Ok(true)
 }
 
-/// Removes a file from Git and commits the removal with the provided commit message.
+/// Removes a file (or directory) from Git and commits the removal with the provided commit message.
 ///
-/// This function runs `git rm <file_path>` to remove the file from version control,
-/// then stages and commits the removal with `git commit -m <message> <file_path>`.
+/// The path is expected to have been deleted from the working tree already. Like
+/// [`git_add_file`], this function looks for a Git repository starting from the
+/// path's parent directory and returns `Ok(())` without doing anything if there is none.
+/// It runs `git rm -r --ignore-unmatch <file_path>` to stage the deletion and, if
+/// anything was staged, `git commit -m <message> <file_path>`.
 ///
 /// # Arguments
 ///
-/// * `file_path` - The path to the file that should be removed from Git.
-/// * `message` - The commit message to use when committing the file removal.
+/// * `file_path` - The path of the removed file or directory.
+/// * `message` - The commit message to use when committing the removal.
+///
+/// # Returns
+///
+/// * `Ok(())` if the removal is committed, there was nothing tracked to remove, or the
+///   path is not in a Git repository.
+/// * `Err(StoreError::GitFailed)` if `git rm` or `git commit` fails.
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,no_run
+/// # use password_store_rs::integrations::git::git_remove_file;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
 /// // Remove a file from Git with a commit message.
-/// git_remove_file("path/to/file.txt", "Remove file.txt from repository");
+/// git_remove_file("/path/to/store/example.com.gpg", "Remove example.com from store.")?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn _git_remove_file(file_path: &str, message: &str) {
-    use std::process::Command;
-
-    // Run "git rm <file_path>".
-    let rm_status = Command::new("git").args(&["rm", file_path]).status();
-
-    match rm_status {
-        Ok(status) if status.success() => {
-            // File successfully removed.
-        }
-        Ok(status) => {
-            println!("Warning: 'git rm' returned non-zero status: {}", status);
-        }
-        Err(e) => {
-            println!(
-                "Warning: Failed to execute 'git rm' for {}: {}",
-                file_path, e
-            );
-        }
+pub fn git_remove_file(file_path: &str, message: &str) -> Result<(), StoreError> {
+    let file_parent = parent_dir(file_path)?;
+
+    // If the file is not inside a Git repository, return Ok(()) silently.
+    if !is_git_repo(file_parent) {
+        return Ok(());
+    }
+
+    // Run "git rm -r --ignore-unmatch <file_path>" to stage the deletion.
+    run_git(
+        file_parent,
+        &["rm", "-q", "-r", "--ignore-unmatch", "--", file_path],
+    )?;
+
+    // Skip the commit if the path was never tracked.
+    let staged = git_output(
+        file_parent,
+        &["diff", "--cached", "--quiet", "--", file_path],
+    )?;
+    if staged.status.success() {
+        return Ok(());
     }
 
     // Run "git commit -m <message> <file_path>" to commit the removal.
-    let commit_status = Command::new("git")
-        .args(&["commit", "-m", message, file_path])
-        .status();
-
-    match commit_status {
-        Ok(status) if status.success() => {
-            // Commit successful.
-        }
-        Ok(status) => {
-            println!("Warning: 'git commit' returned non-zero status: {}", status);
-        }
-        Err(e) => {
-            println!(
-                "Warning: Failed to execute 'git commit' for {}: {}",
-                file_path, e
-            );
-        }
+    run_git(
+        file_parent,
+        &["commit", "-q", "-m", message, "--", file_path],
+    )
+}
+
+/// Stages every change under the given paths and records them in a single commit.
+///
+/// Paths that still exist are staged with `git add -A`, and paths that no longer exist
+/// have their deletion staged with `git rm --cached`. Only the files that actually
+/// changed are passed to `git commit`, so unrelated staged changes are left alone. The
+/// journal and temporary files of an unfinished re-encryption (see
+/// [`crate::journal::ReencryptJournal`]) are never staged. If `store` is not inside a Git
+/// repository or nothing changed, this returns `Ok(())` without committing.
+///
+/// # Arguments
+///
+/// * `store` - The root directory of the password store, where Git is run.
+/// * `paths` - The files or directories inside the password store to commit.
+/// * `message` - The commit message.
+///
+/// # Returns
+///
+/// * `Ok(())` if the changes were committed, or there was nothing to commit.
+/// * `Err(StoreError::GitFailed)` if any Git command fails.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::git::git_commit_paths;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// // Record a rename as a single commit.
+/// git_commit_paths(
+///     Path::new("/path/to/store"),
+///     &["/path/to/store/old.gpg", "/path/to/store/new.gpg"],
+///     "Rename old to new.",
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn git_commit_paths(store: &Path, paths: &[&str], message: &str) -> Result<(), StoreError> {
+    // If the store is not inside a Git repository, return Ok(()) silently.
+    if !is_git_repo(store) {
+        return Ok(());
+    }
+
+    let excluded = excluded_pathspecs();
+    for path in paths {
+        let mut args: Vec<&str> = if Path::new(path).exists() {
+            vec!["add", "-A", "--", path]
+        } else {
+            vec!["rm", "-q", "-r", "--cached", "--ignore-unmatch", "--", path]
+        };
+        args.extend(excluded.iter().map(String::as_str));
+        run_git(store, &args)?;
+    }
+
+    let changed = Command::new("git")
+        .args(["diff", "--cached", "--name-only", "-z", "--"])
+        .args(paths)
+        .args(&excluded)
+        .current_dir(store)
+        .output()
+        .map_err(|e| git_not_run("diff", e))?;
+    if !changed.status.success() {
+        return Err(StoreError::git(
+            "git diff command failed",
+            changed.status,
+            &changed.stderr,
+        ));
+    }
+
+    let changed = String::from_utf8_lossy(&changed.stdout);
+    let files: Vec<&str> = changed.split('\0').filter(|f| !f.is_empty()).collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    // `git diff --name-only` prints paths relative to the repository root.
+    let pathspecs: Vec<String> = files.iter().map(|f| format!(":/{}", f)).collect();
+    let mut args = vec!["commit", "-q", "-m", message, "--"];
+    args.extend(pathspecs.iter().map(String::as_str));
+    run_git(store, &args)
+}
+
+/// Returns the pathspecs, relative to the store root, that leave out the re-encryption
+/// journal and the temporary files of re-encrypted entries.
+fn excluded_pathspecs() -> Vec<String> {
+    vec![
+        format!(":(exclude){}", JOURNAL_DIR),
+        ":(exclude,glob)**/.*.reencrypt-tmp".to_string(),
+    ]
+}
+
+/// Returns the parent directory of `file_path`, where Git is run for that file.
+fn parent_dir(file_path: &str) -> Result<&Path, StoreError> {
+    Path::new(file_path).parent().ok_or_else(|| {
+        StoreError::InvalidInput(format!(
+            "Could not determine parent directory of {}",
+            file_path
+        ))
+    })
+}
+
+/// Runs `git <args>` in `dir` and returns its output.
+///
+/// Standard output and standard error are both captured, so that nothing is printed and
+/// the error can be reported if the command fails. A non-success exit status is not an
+/// error here.
+fn git_output(dir: &Path, args: &[&str]) -> Result<Output, StoreError> {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| git_not_run(args[0], e))
+}
+
+/// Runs `git <args>` in `dir`, failing with [`StoreError::GitFailed`] unless it succeeds.
+fn run_git(dir: &Path, args: &[&str]) -> Result<(), StoreError> {
+    let output = git_output(dir, args)?;
+    if !output.status.success() {
+        return Err(StoreError::git(
+            format!("git {} command failed", args[0]),
+            output.status,
+            &output.stderr,
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the error for a `git <command>` process that could not be started at all.
+fn git_not_run(command: &str, e: std::io::Error) -> StoreError {
+    StoreError::GitFailed {
+        context: format!("Failed to execute git {}", command),
+        status: None,
+        stderr: e.to_string(),
     }
 }
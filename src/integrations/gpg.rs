@@ -1,251 +1,931 @@
+use crate::crypto::CryptoBackend;
+use crate::error::{GpgProblem, StoreError};
+use crate::journal::{rename_synced, sync_file, temp_path, ReencryptJournal};
+use crate::utils::{executable_in_path, parallel_map};
 use std::collections::HashSet;
-use std::io::Write;
-use std::path::Path;
-use std::process::{exit, Command, Stdio};
+use std::env;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
 
-/// Signs the specified file using GPG with a detached signature.
+/// The [`CryptoBackend`] that runs the `gpg` executable for every operation.
 ///
-/// This function attempts to sign the file located at `file_path` by invoking GPG with the
-/// `--detach-sign` option. It incorporates additional configuration as follows:
+/// Keys come from the user's GnuPG keyring, and decryption uses `gpg-agent` and pinentry
+/// as usual. This is the backend [`crate::PasswordStore::open`] uses.
 ///
-/// - Additional GPG options are taken from the `GPG_OPTS` environment variable (if set).
-/// - Default signing keys are read from the `PASSWORD_STORE_SIGNING_KEY` environment variable (if set),
-///   with each key provided via a `--default-key` argument.
-/// - The GPG executable is determined by the `GPG` environment variable, defaulting to `"gpg"` if not set.
+/// Every invocation is built by [`GpgBackend::command`], so the executable and options can
+/// be configured in one place, like pass does with `GPG` and `PASSWORD_STORE_GPG_OPTS`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::GpgBackend;
+/// # use password_store_rs::CryptoBackend;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let backend = GpgBackend::default();
+/// let plaintext = backend.decrypt(Path::new("/path/to/store/example.com.gpg"))?;
+///
+/// // Use a separate keyring, e.g. in tests.
+/// let backend = GpgBackend::with_home("/tmp/test-gnupg");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct GpgBackend {
+    /// The GnuPG home directory, passed as `GNUPGHOME`, or `None` for the user's own.
+    home: Option<PathBuf>,
+}
+
+impl GpgBackend {
+    /// Creates a backend that uses the keyring in `home` instead of the user's own, by
+    /// setting `GNUPGHOME` for every invocation.
+    pub fn with_home(home: impl Into<PathBuf>) -> GpgBackend {
+        GpgBackend {
+            home: Some(home.into()),
+        }
+    }
+
+    /// Returns a [`Command`] that runs GPG, ready for the operation's own arguments.
+    ///
+    /// The command is set up like pass sets up its `gpg` calls:
+    ///
+    /// - The executable is [`gpg_executable`].
+    /// - The options from [`gpg_options`] come first: `PASSWORD_STORE_GPG_OPTS`,
+    ///   `GPG_OPTS`, and `--quiet --yes --compress-algo=none --no-encrypt-to`.
+    /// - `GNUPGHOME` is set if the backend was created with [`GpgBackend::with_home`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use password_store_rs::integrations::gpg::GpgBackend;
+    /// # fn main() -> std::io::Result<()> {
+    /// let output = GpgBackend::default()
+    ///     .command()
+    ///     .args(["--list-keys", "alice@example.com"])
+    ///     .output()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(gpg_executable());
+        command.args(gpg_options());
+        if let Some(home) = &self.home {
+            command.env("GNUPGHOME", home);
+        }
+        command
+    }
+
+    /// Returns [`GpgBackend::command`] with `--status-fd=2`, for invocations whose standard
+    /// error is captured.
+    ///
+    /// GPG then writes its machine-readable `[GNUPG:]` status lines among its messages, which
+    /// [`gpg_failed`] reads to explain a failure (see [`GpgProblem`]).
+    fn status_command(&self) -> Command {
+        let mut command = self.command();
+        command.arg("--status-fd=2");
+        command
+    }
+}
+
+impl CryptoBackend for GpgBackend {
+    /// Encrypts `contents` for `recipients` and writes the result to `output`.
+    ///
+    /// This runs `gpg --encrypt --batch -r <recipient>... --output <output>`, passing
+    /// each recipient as its own `-r` argument, and feeds `contents` through standard input.
+    /// An existing file at `output` is overwritten.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the file was written successfully.
+    /// * `Err(StoreError::GpgFailed)` if GPG cannot be executed or exits with a non-success
+    ///   status. A recipient that cannot be used, e.g. because its key is missing, expired or
+    ///   untrusted, is named in its [`GpgProblem`].
+    fn encrypt(
+        &self,
+        contents: &[u8],
+        recipients: &[String],
+        output: &Path,
+    ) -> Result<(), StoreError> {
+        let mut child = self
+            .status_command()
+            .args(["--encrypt", "--batch"])
+            .args(recipients.iter().flat_map(|r| ["-r", r.as_str()]))
+            .arg("--output")
+            .arg(output)
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                gpg_not_run(
+                    &format!("Failed to execute gpg for {}", output.display()),
+                    e,
+                )
+            })?;
+
+        if let Some(mut child_stdin) = child.stdin.take() {
+            // GPG closes its input early when it rejects a recipient; its exit status and
+            // standard error explain why, so a broken pipe is not reported on its own.
+            match child_stdin.write_all(contents) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                    return Err(StoreError::io(
+                        format!("Failed to write to gpg stdin for {}", output.display()),
+                        e,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        let result = child.wait_with_output().map_err(|e| {
+            StoreError::io(format!("Failed to wait on gpg for {}", output.display()), e)
+        })?;
+        if !result.status.success() {
+            return Err(gpg_failed(
+                format!("GPG encryption failed for {}", output.display()),
+                &result,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Decrypts a `.gpg` file and returns its plaintext contents.
+    ///
+    /// This runs `gpg -d <path>` and captures standard output. Standard error is captured too,
+    /// and is included in the error if decryption fails.
+    ///
+    /// When the entry is encrypted only for keys that are not in the secret keyring, the error
+    /// names them. GPG does not report this with `--quiet`, so the keys the entry is encrypted
+    /// to are then looked up with `gpg --list-secret-keys`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` with the decrypted bytes.
+    /// * `Err(StoreError::GpgFailed)` if GPG cannot be executed or exits with a non-success
+    ///   status, with a [`GpgProblem`] when the cause is known.
+    fn decrypt(&self, path: &Path) -> Result<Vec<u8>, StoreError> {
+        let output = self
+            .status_command()
+            .arg("-d")
+            .arg(path)
+            .output()
+            .map_err(|e| {
+                gpg_not_run(&format!("Failed to execute gpg for {}", path.display()), e)
+            })?;
+
+        if !output.status.success() {
+            let mut status = GpgStatus::parse(&output.stderr);
+            if status.decryption_failed
+                && status.no_secret_key.is_empty()
+                && !status.pinentry_launched
+                && !status.encrypted_to.is_empty()
+            {
+                let secret_keys = self.secret_key_ids()?;
+                if !status
+                    .encrypted_to
+                    .iter()
+                    .any(|key_id| secret_keys.contains(key_id))
+                {
+                    status.no_secret_key = status.encrypted_to.clone();
+                }
+            }
+            return Err(status.error(
+                format!("GPG decryption failed for {}", path.display()),
+                &output,
+            ));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Signs the file at `path` with a detached signature written to `<path>.sig`.
+    ///
+    /// This runs [`GpgBackend::command`] with the `--detach-sign` option, passing each of
+    /// `signing_keys` as a `--default-key` argument.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the signing process completes successfully.
+    /// * `Err(StoreError::GpgFailed)` if there is an error invoking GPG or if GPG returns a non-success exit status.
+    fn sign(&self, path: &Path, signing_keys: &[String]) -> Result<(), StoreError> {
+        // Build the GPG command.
+        let mut cmd = self.status_command();
+        for key in signing_keys {
+            cmd.arg("--default-key").arg(key);
+        }
+        // Add the --detach-sign flag and the file to sign.
+        cmd.arg("--detach-sign").arg(path);
+
+        // Execute the command and capture its output.
+        let output = cmd
+            .output()
+            .map_err(|e| gpg_not_run(&format!("Failed to execute {}", gpg_executable()), e))?;
+
+        if !output.status.success() {
+            return Err(gpg_failed(
+                format!("GPG failed when signing file {}", path.display()),
+                &output,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Runs `gpg --status-fd=2 --verify <signature> <path>` and returns the fingerprints of
+    /// its `VALIDSIG` lines.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HashSet<String>)` with the signing and primary key fingerprints, empty if GPG
+    ///   rejects the signature.
+    /// * `Err(StoreError::GpgFailed)` if GPG cannot be executed.
+    fn verify(&self, path: &Path, signature: &Path) -> Result<HashSet<String>, StoreError> {
+        let output = self
+            .status_command()
+            .arg("--verify")
+            .arg(signature)
+            .arg(path)
+            .output()
+            .map_err(|e| gpg_not_run("Failed to execute gpg --verify", e))?;
+
+        if !output.status.success() {
+            return Ok(HashSet::new());
+        }
+        Ok(GpgStatus::parse(&output.stderr).valid_signatures)
+    }
+
+    /// Returns the fingerprints of the primary keys in the keyring.
+    ///
+    /// This parses the output of `gpg --list-keys --with-colons`, taking the fingerprint
+    /// (`fpr`) line that immediately follows each `pub` (public key) line. Subkey (`sub:`)
+    /// entries and their fingerprints are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<String>)` with one fingerprint per primary key, in the order GPG lists them.
+    /// * `Err(StoreError::GpgFailed)` if the `gpg` command fails to execute successfully.
+    fn list_keys(&self) -> Result<Vec<String>, StoreError> {
+        let stdout = self.list_keys_with_colons()?;
+        let mut fingerprints = Vec::new();
+        let mut in_pub = false;
+
+        for line in stdout.lines() {
+            if line.starts_with("pub:") {
+                in_pub = true;
+            } else if line.starts_with("sub:") {
+                in_pub = false;
+            } else if in_pub && line.starts_with("fpr:") {
+                if let Some(fpr) = line.split(':').nth(9) {
+                    fingerprints.push(fpr.to_string());
+                }
+                in_pub = false;
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    /// Returns whether `gpg --list-keys <key_id>` knows a public key.
+    fn key_exists(&self, key_id: &str) -> Result<bool, StoreError> {
+        let output = self
+            .status_command()
+            .args(["--list-keys", key_id])
+            .output()
+            .map_err(|e| gpg_not_run("Error checking for GPG key", e))?;
+        Ok(output.status.success() && !output.stdout.is_empty())
+    }
+
+    /// Runs `gpg --status-fd=2 --decrypt --list-only <path>` and returns the key IDs of its
+    /// `ENC_TO` lines.
+    ///
+    /// Nothing is decrypted, so no passphrase is asked for.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(HashSet<String>))` with the key IDs the file is encrypted to.
+    /// * `Ok(None)` if GPG cannot read the file.
+    /// * `Err(StoreError::GpgFailed)` if GPG cannot be executed.
+    fn encrypted_key_ids(&self, path: &Path) -> Result<Option<HashSet<String>>, StoreError> {
+        let output = self
+            .status_command()
+            .args(["--decrypt", "--list-only"])
+            .arg(path)
+            .output()
+            .map_err(|e| {
+                gpg_not_run(&format!("Failed to execute gpg for {}", path.display()), e)
+            })?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(Some(
+            GpgStatus::parse(&output.stderr)
+                .encrypted_to
+                .into_iter()
+                .collect(),
+        ))
+    }
+
+    /// Returns the key IDs of the encryption subkeys GPG would use for `recipients`.
+    ///
+    /// This lists the keys with `gpg --list-keys --with-colons <recipient>...` and picks one
+    /// encryption key per key (see [`encryption_key_ids`]).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(HashSet<String>))` with one key ID per distinct recipient key.
+    /// * `Ok(None)` if a recipient is not in the keyring or has no usable encryption key.
+    /// * `Err(StoreError::GpgFailed)` if GPG cannot be executed.
+    fn recipient_key_ids(
+        &self,
+        recipients: &[String],
+    ) -> Result<Option<HashSet<String>>, StoreError> {
+        let output = self
+            .status_command()
+            .args(["--list-keys", "--with-colons"])
+            .args(recipients)
+            .output()
+            .map_err(|e| gpg_not_run("Failed to list GPG keys", e))?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+        Ok(encryption_key_ids(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Generates a new GPG key using the interactive `gpg --full-gen-key` command
+    /// and returns the fingerprint of the newly created **primary** key.
+    ///
+    /// After generation, the fingerprint of the first primary key in the keyring is
+    /// returned (see [`CryptoBackend::list_keys`]), which is typically used for signing and
+    /// identifying the key.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` representing the fingerprint of the newly generated primary key.
+    /// * `Err(StoreError::GpgFailed)` if:
+    ///   - The `gpg` command fails to execute.
+    ///   - The user cancels or fails to complete the key generation.
+    ///   - The primary key fingerprint cannot be extracted after key creation.
+    ///
+    /// # Notes
+    ///
+    /// - The GPG interface is interactive and requires user input, so this is the one
+    ///   invocation without `--status-fd`: its standard error is the terminal.
+    fn generate_key(&self) -> Result<String, StoreError> {
+        let status = self
+            .command()
+            .arg("--full-gen-key")
+            .status()
+            .map_err(|e| gpg_not_run("Failed to execute gpg --full-gen-key", e))?;
+
+        if !status.success() {
+            return Err(StoreError::gpg(
+                "Failed to generate a new GPG key",
+                status,
+                &[],
+            ));
+        }
+
+        self.list_keys()?
+            .into_iter()
+            .next()
+            .ok_or_else(|| StoreError::GpgFailed {
+                context: "Failed to extract primary fingerprint from generated key".to_string(),
+                status: None,
+                stderr: String::new(),
+                problem: None,
+            })
+    }
+}
+
+/// Verifies the detached signature of `file_path` against the keys in `PASSWORD_STORE_SIGNING_KEY`.
+///
+/// If `PASSWORD_STORE_SIGNING_KEY` is unset or empty, no verification is done. Otherwise the
+/// signature must exist at `<file_path>.sig`, and `backend` must report a valid signature
+/// whose signing key or primary key fingerprint is one of the listed fingerprints (see
+/// [`CryptoBackend::verify`]). This guards against a tampered `.gpg-id` being used to
+/// encrypt secrets for an attacker.
 ///
 /// # Arguments
 ///
-/// * `file_path` - The path to the file that should be signed.
+/// * `backend` - The backend that checks the signature.
+/// * `file_path` - The signed file, typically a `.gpg-id`.
 ///
 /// # Returns
 ///
-/// * `Ok(())` if the signing process completes successfully.
-/// * `Err(String)` if there is an error invoking GPG or if GPG returns a non-success exit status.
+/// * `Ok(())` if verification is disabled or the signature is valid and made by a listed key.
+/// * `Err(StoreError::SignatureMissing)` if the signature does not exist.
+/// * `Err(StoreError::SignatureInvalid)` if it is invalid or made by a key that is not listed.
+/// * `Err(StoreError::GpgFailed)` if GPG cannot be executed.
 ///
 /// # Examples
 ///
-/// ```rust
-/// // Attempt to sign a file located at "/path/to/.gpg-id"
-/// match sign_file("/path/to/.gpg-id") {
-///     Ok(()) => println!("File signed successfully."),
-///     Err(e) => eprintln!("Signing failed: {}", e),
-/// }
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::{verify_file, GpgBackend};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// verify_file(&GpgBackend::default(), Path::new("/path/to/store/.gpg-id"))?;
+/// # Ok(())
+/// # }
 /// ```
-pub fn sign_file(file_path: &str) -> Result<(), String> {
-    // Use the GPG environment variable if set, otherwise default to "gpg"
-    let gpg_executable = std::env::var("GPG").unwrap_or_else(|_| "gpg".to_string());
-
-    // Retrieve additional GPG options from the GPG_OPTS environment variable.
-    let gpg_opts = std::env::var("GPG_OPTS").unwrap_or_default();
-    let gpg_opts_args: Vec<&str> = gpg_opts.split_whitespace().collect();
-
-    // Prepare signing key arguments from PASSWORD_STORE_SIGNING_KEY.
-    let mut signing_args: Vec<String> = Vec::new();
-    if let Ok(signing_keys) = std::env::var("PASSWORD_STORE_SIGNING_KEY") {
-        if !signing_keys.trim().is_empty() {
-            for key in signing_keys.split_whitespace() {
-                signing_args.push("--default-key".to_string());
-                signing_args.push(key.to_string());
-            }
-        }
+pub fn verify_file(backend: &dyn CryptoBackend, file_path: &Path) -> Result<(), StoreError> {
+    let signing_keys = std::env::var("PASSWORD_STORE_SIGNING_KEY").unwrap_or_default();
+    if signing_keys.trim().is_empty() {
+        return Ok(());
     }
 
-    // Build the GPG command.
-    let mut cmd = std::process::Command::new(&gpg_executable);
-
-    // Add GPG options.
-    for arg in gpg_opts_args {
-        cmd.arg(arg);
+    let mut sig_path = file_path.as_os_str().to_owned();
+    sig_path.push(".sig");
+    let sig_path = PathBuf::from(sig_path);
+    if !sig_path.is_file() {
+        return Err(StoreError::SignatureMissing(file_path.to_path_buf()));
     }
-    // Add signing key options.
-    for arg in signing_args {
-        cmd.arg(arg);
+
+    let fingerprints = backend.verify(file_path, &sig_path)?;
+    let trusted = signing_keys
+        .split_whitespace()
+        .map(|key| key.to_uppercase())
+        .any(|key| fingerprints.contains(&key));
+
+    if !trusted {
+        return Err(StoreError::SignatureInvalid(file_path.to_path_buf()));
     }
-    // Add the --detach-sign flag and the file to sign.
-    cmd.arg("--detach-sign").arg(file_path);
 
-    // Execute the command and capture its output.
-    let output = match cmd
-        .output()
-        .map_err(|e| format!("Failed to execute {}: {}", gpg_executable, e))
-    {
-        Ok(o) => o,
-        Err(err) => return Err(err),
+    Ok(())
+}
+
+/// Finds the `.gpg-id` file, or the `id_file` of another backend such as `.age-recipients`,
+/// that governs `path`.
+///
+/// Starting at `path` itself when it is a directory, or at its parent otherwise, this
+/// walks up the directory tree until an `id_file` is found. The search never goes
+/// above `store`. The directories do not need to exist, so the recipients of a
+/// location can be determined before an entry is written there.
+///
+/// # Arguments
+///
+/// * `store` - The root directory of the password store.
+/// * `path` - An entry file or directory inside the password store.
+/// * `id_file` - The name of the recipients file, usually [`CryptoBackend::recipients_file`].
+///
+/// # Returns
+///
+/// * `Some(PathBuf)` with the path of the nearest `id_file`.
+/// * `None` if neither `path` nor any of its ancestors up to `store` contain one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::find_gpg_id;
+/// // With .gpg-id files at the store root and in "work/", this returns "/path/to/store/work/.gpg-id".
+/// let gpg_id = find_gpg_id(
+///     Path::new("/path/to/store"),
+///     Path::new("/path/to/store/work/vpn.gpg"),
+///     ".gpg-id",
+/// );
+/// ```
+pub fn find_gpg_id(store: &Path, path: &Path, id_file: &str) -> Option<PathBuf> {
+    let mut current = if path.is_dir() {
+        Some(path)
+    } else {
+        path.parent()
     };
 
-    if !output.status.success() {
-        return Err(format!(
-            "GPG exited with status {} when signing file {}",
-            output.status, file_path
-        ));
+    while let Some(dir) = current {
+        if !dir.starts_with(store) {
+            break;
+        }
+        let candidate = dir.join(id_file);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir == store {
+            break;
+        }
+        current = dir.parent();
     }
 
-    Ok(())
+    None
 }
 
-/// Reencrypts all `.gpg` files in the specified directory tree using the recipient defined in the `.gpg-id` file.
+/// Reads the recipients listed in a `.gpg-id` file.
+///
+/// The file lists one GPG key identifier per line. Everything after a `#` is a comment,
+/// surrounding whitespace is ignored, and blank lines are skipped. When
+/// `PASSWORD_STORE_SIGNING_KEY` is set, the file's signature is checked with [`verify_file`]
+/// before it is read.
+///
+/// # Arguments
+///
+/// * `backend` - The backend that checks the signature.
+/// * `gpg_id_path` - The `.gpg-id` file to read.
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` with the recipients in file order.
+/// * `Err(StoreError)` if the signature check fails, the file cannot be read, or it lists no recipients.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::{read_gpg_recipients, GpgBackend};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// // A .gpg-id containing:
+/// //   # ops team
+/// //   alice@example.com
+/// //   34E8F4A6A3851A5C  # bob
+/// let recipients = read_gpg_recipients(&GpgBackend::default(), Path::new("/path/to/store/.gpg-id"))?;
+/// assert_eq!(recipients, vec!["alice@example.com", "34E8F4A6A3851A5C"]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn read_gpg_recipients(
+    backend: &dyn CryptoBackend,
+    gpg_id_path: &Path,
+) -> Result<Vec<String>, StoreError> {
+    verify_file(backend, gpg_id_path)?;
+
+    let contents = std::fs::read_to_string(gpg_id_path)
+        .map_err(|e| StoreError::io(format!("Failed to read {}", gpg_id_path.display()), e))?;
+
+    let recipients: Vec<String> = contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if recipients.is_empty() {
+        return Err(StoreError::RecipientMissing(format!(
+            "No recipient found in {}",
+            gpg_id_path.display()
+        )));
+    }
+    Ok(recipients)
+}
+
+/// Resolves the recipients that entries at `path` must be encrypted for.
+///
+/// This combines [`find_gpg_id`], looking for the backend's
+/// [`CryptoBackend::recipients_file`], and [`read_gpg_recipients`].
+///
+/// # Arguments
+///
+/// * `backend` - The backend that checks the `.gpg-id` signature.
+/// * `store` - The root directory of the password store.
+/// * `path` - An entry file or directory inside the password store. It does not need to exist.
+///
+/// # Returns
+///
+/// * `Ok(Vec<String>)` with the recipients from the governing `.gpg-id` (or `.age-recipients`).
+/// * `Err(StoreError::RecipientMissing)` if no such file governs `path`.
+/// * `Err(StoreError)` if the `.gpg-id` cannot be read (see [`read_gpg_recipients`]).
+pub fn resolve_recipients(
+    backend: &dyn CryptoBackend,
+    store: &Path,
+    path: &Path,
+) -> Result<Vec<String>, StoreError> {
+    let id_file = backend.recipients_file();
+    let gpg_id = find_gpg_id(store, path, id_file).ok_or_else(|| {
+        StoreError::RecipientMissing(format!(
+            "no {} found for {}. Try \"pass init\".",
+            id_file,
+            path.display()
+        ))
+    })?;
+    read_gpg_recipients(backend, &gpg_id)
+}
+
+/// How far a run of [`reencrypt_path`] has got, reported after every entry it attempts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReencryptProgress {
+    /// The number of entries attempted so far, including failed ones.
+    pub done: usize,
+    /// The number of attempted entries that could not be re-encrypted.
+    pub failed: usize,
+    /// The number of entries the run re-encrypts.
+    pub total: usize,
+}
+
+/// The outcome of a run of [`reencrypt_path`].
+#[derive(Debug, Default)]
+pub struct ReencryptReport {
+    /// The entries that were re-encrypted, sorted.
+    pub reencrypted: Vec<PathBuf>,
+    /// The number of entries left alone because they were already encrypted for the
+    /// recipients' keys, or finished by the interrupted run this one resumed.
+    pub skipped: usize,
+    /// The entries that could not be re-encrypted, sorted, with the reason. They keep their
+    /// original contents.
+    pub failed: Vec<(PathBuf, StoreError)>,
+}
+
+/// Reencrypts all entries in the specified directory tree using the recipients that govern it.
 ///
 /// This function performs the following steps:
 ///
-/// 1. Verifies that `path` is a directory and reads the recipient from the `.gpg-id` file located in that directory.
-/// 2. Recursively walks through the directory tree rooted at `path`.
-/// 3. For each file that ends with the `.gpg` extension:
-///    - Decrypts the file using `gpg -d`.
-///    - Re-encrypts the decrypted content using `gpg --encrypt --yes --batch --recipient <recipient> --output <file>`
-///      so that the file is re-encrypted with the current GPG configuration.
-/// 4. Returns an `Ok(())` on success, or an `Err(String)` containing an error message if any step fails.
+/// 1. Verifies that `path` is a directory and resolves its recipients from the nearest `.gpg-id`
+///    at or above it (see [`resolve_recipients`]).
+/// 2. Starts a [`ReencryptJournal`], which resumes an interrupted run for the same folder and
+///    recipients, rolls back an interrupted run of the same folder for other recipients, and
+///    refuses to start while a run of another folder is unfinished.
+/// 3. Recursively walks through the directory tree rooted at `path`. Subfolders that contain their
+///    own `.gpg-id` are skipped, since their entries are governed by a different recipient list,
+///    and so are hidden directories such as `.git`. Entries that are already encrypted for the
+///    recipients' keys, or were finished by a resumed run, are skipped as well.
+/// 4. Re-encrypts each remaining file that ends with the backend's extension (see
+///    [`CryptoBackend::extension`]) on a bounded pool of worker threads (see
+///    [`parallel_map`]), records it in the journal, and calls `progress` after each one.
+/// 5. Removes the journal once every entry is done.
+///
+/// An entry is never overwritten in place: the new file is written next to it, checked and
+/// synced, then renamed over it. If the run stops early, every entry is either its original or
+/// its re-encrypted version, and the next run picks up where this one stopped.
+///
+/// An entry that fails to re-encrypt does not stop the run. It keeps its original contents and
+/// is listed in the report, and the journal is kept so that running again retries it.
 ///
 /// # Arguments
 ///
-/// * `path` - A string slice representing the root directory of the password store to reencrypt.
+/// * `backend` - The backend that decrypts and encrypts the entries.
+/// * `store` - The root directory of the password store.
+/// * `path` - The directory to reencrypt: the store root or one of its subfolders.
+/// * `progress` - Called after every attempted entry. It may be called from several threads,
+///   but never from two at once.
 ///
 /// # Returns
 ///
-/// * `Ok(())` if all files are successfully reencrypted.
-/// * `Err(String)` if there is an error reading directories, files, or if any GPG command fails.
+/// * `Ok(ReencryptReport)` with the re-encrypted, skipped and failed entries.
+/// * `Err(StoreError)` if the recipients cannot be resolved, or a directory, an entry's keys
+///   or the journal cannot be read. Nothing is re-encrypted in that case.
 ///
 /// # Examples
 ///
-/// ```rust
-/// match reencrypt_path("/path/to/password-store") {
-///     Ok(()) => println!("Reencryption successful."),
-///     Err(e) => eprintln!("Reencryption failed: {}", e),
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::{reencrypt_path, GpgBackend};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let store = Path::new("/path/to/password-store");
+/// let report = reencrypt_path(&GpgBackend::default(), store, store, &|progress| {
+///     eprint!("\r{}/{}", progress.done, progress.total);
+/// })?;
+/// for (entry, e) in &report.failed {
+///     eprintln!("{}: {}", entry.display(), e);
 /// }
+/// # Ok(())
+/// # }
 /// ```
-pub fn reencrypt_path(path: &str) -> Result<(), String> {
-    let root = Path::new(path);
-    if !root.is_dir() {
-        return Err(format!("Provided path {} is not a directory", path));
+pub fn reencrypt_path(
+    backend: &dyn CryptoBackend,
+    store: &Path,
+    path: &Path,
+    progress: &(dyn Fn(ReencryptProgress) + Sync),
+) -> Result<ReencryptReport, StoreError> {
+    if !path.is_dir() {
+        return Err(StoreError::InvalidInput(format!(
+            "Provided path {} is not a directory",
+            path.display()
+        )));
     }
 
-    // Read the recipient from the .gpg-id file in the root directory.
-    let gpg_id_path = root.join(".gpg-id");
-    let recipient = match std::fs::read_to_string(&gpg_id_path) {
-        Ok(content) => content.trim().to_string(),
-        Err(e) => return Err(format!("Failed to read {}: {}", gpg_id_path.display(), e)),
+    // Resolve the recipients governing the root directory.
+    let recipients = resolve_recipients(backend, store, path)?;
+
+    let journal = ReencryptJournal::begin(store, path, &recipients)?;
+    let entries = list_entries(backend, path)?;
+    let total = entries.len();
+    let pending = entries
+        .into_iter()
+        .filter(|entry| !journal.is_done(entry))
+        .collect();
+    let stale = filter_stale(backend, pending, &recipients)?;
+
+    let counts = Mutex::new(ReencryptProgress {
+        total: stale.len(),
+        ..ReencryptProgress::default()
+    });
+    let results = parallel_map(&stale, |entry| {
+        let result = encrypt_to_temp(backend, entry, &recipients)
+            .and_then(|temp| journal.replace(entry, &temp));
+        // The lock is held while reporting, so the counts passed to `progress` never go back.
+        let mut counts = counts.lock().unwrap();
+        counts.done += 1;
+        if result.is_err() {
+            counts.failed += 1;
+        }
+        progress(*counts);
+        result
+    });
+
+    let mut report = ReencryptReport {
+        skipped: total - stale.len(),
+        ..ReencryptReport::default()
     };
-    if recipient.is_empty() {
-        return Err("No recipient found in .gpg-id".to_string());
+    for (entry, result) in stale.into_iter().zip(results) {
+        match result {
+            Ok(()) => report.reencrypted.push(entry),
+            Err(e) => report.failed.push((entry, e)),
+        }
     }
 
-    // Recursively process the directory.
-    fn reencrypt_dir(dir: &Path, recipient: &str) -> Result<(), String> {
-        let entries = match std::fs::read_dir(dir) {
-            Ok(entries) => entries,
-            Err(e) => return Err(format!("Failed to read directory {}: {}", dir.display(), e)),
-        };
+    if report.failed.is_empty() {
+        journal.finish()?;
+    }
+    Ok(report)
+}
 
-        for entry in entries {
-            let entry = match entry {
-                Ok(ent) => ent,
-                Err(e) => return Err(format!("Failed to read directory entry: {}", e)),
-            };
-            let path = entry.path();
+/// Returns the entries under `path` that are not encrypted for exactly `recipients`.
+///
+/// The entries are those [`reencrypt_path`] covers: subfolders with their own `.gpg-id` and
+/// hidden directories are skipped. An entry is up to date when the key IDs it is encrypted to
+/// (see [`CryptoBackend::encrypted_key_ids`]) are the encryption keys of `recipients` (see
+/// [`CryptoBackend::recipient_key_ids`]). When the backend cannot tell either, every entry is
+/// returned. The entries are inspected on a bounded pool of worker threads.
+///
+/// # Arguments
+///
+/// * `backend` - The backend that inspects the entries and keys.
+/// * `path` - The directory to check: the store root or one of its subfolders.
+/// * `recipients` - The recipients the entries should be encrypted for.
+///
+/// # Returns
+///
+/// * `Ok(Vec<PathBuf>)` with the paths of the entries to re-encrypt, sorted. A missing `path`
+///   has none.
+/// * `Err(StoreError)` if a directory cannot be read or the backend fails.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::{stale_entries, GpgBackend};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let store = Path::new("/path/to/password-store");
+/// let recipients = vec!["alice@example.com".to_string()];
+/// for entry in stale_entries(&GpgBackend::default(), store, &recipients)? {
+///     println!("{} would be re-encrypted", entry.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn stale_entries(
+    backend: &dyn CryptoBackend,
+    path: &Path,
+    recipients: &[String],
+) -> Result<Vec<PathBuf>, StoreError> {
+    filter_stale(backend, list_entries(backend, path)?, recipients)
+}
 
-            if path.is_dir() {
-                if let Err(e) = reencrypt_dir(&path, recipient) {
-                    return Err(e);
-                }
-            } else {
-                if let Some(ext) = path.extension() {
-                    if ext == "gpg" {
-                        // Decrypt the file.
-                        let output = match Command::new("gpg")
-                            .arg("-d")
-                            .arg(
-                                path.to_str()
-                                    .ok_or_else(|| format!("Invalid path: {}", path.display()))
-                                    .unwrap(),
-                            )
-                            .output()
-                        {
-                            Ok(out) => out,
-                            Err(e) => {
-                                return Err(format!(
-                                    "Failed to execute gpg for {}: {}",
-                                    path.display(),
-                                    e
-                                ))
-                            }
-                        };
-                        if !output.status.success() {
-                            return Err(format!(
-                                "GPG decryption failed for {} with status {}",
-                                path.display(),
-                                output.status
-                            ));
-                        }
-                        let decrypted = output.stdout;
-
-                        // Re-encrypt the content using the provided recipient.
-                        // First, spawn the GPG process for encryption.
-                        let file_str = match path.to_str() {
-                            Some(s) => s,
-                            None => return Err(format!("Invalid path: {}", path.display())),
-                        };
-                        let mut child = match Command::new("gpg")
-                            .args(&[
-                                "--encrypt",
-                                "--yes",
-                                "--batch",
-                                "--recipient",
-                                recipient,
-                                "--output",
-                                file_str,
-                            ])
-                            .stdin(Stdio::piped())
-                            .spawn()
-                        {
-                            Ok(child) => child,
-                            Err(e) => {
-                                return Err(format!(
-                                    "Failed to reencrypt {}: {}",
-                                    path.display(),
-                                    e
-                                ))
-                            }
-                        };
-
-                        // Write the decrypted content to the child process's stdin.
-                        let child_stdin = match child.stdin.as_mut() {
-                            Some(stdin) => stdin,
-                            None => return Err("Failed to open gpg stdin".to_string()),
-                        };
-
-                        if let Err(e) = child_stdin.write_all(&decrypted) {
-                            return Err(format!(
-                                "Failed to write to gpg stdin for {}: {}",
-                                path.display(),
-                                e
-                            ));
-                        }
-
-                        // Wait for the encryption process to finish.
-                        let status = match child.wait() {
-                            Ok(status) => status,
-                            Err(e) => {
-                                return Err(format!(
-                                    "Failed to wait on gpg for {}: {}",
-                                    path.display(),
-                                    e
-                                ))
-                            }
-                        };
-                        if !status.success() {
-                            return Err(format!(
-                                "GPG re-encryption failed for {} with status {}",
-                                path.display(),
-                                status
-                            ));
-                        }
-                    }
-                }
+/// Returns the sorted entries under `path` that share its recipients. A missing `path` has none.
+fn list_entries(backend: &dyn CryptoBackend, path: &Path) -> Result<Vec<PathBuf>, StoreError> {
+    let mut entries = Vec::new();
+    if path.is_dir() {
+        collect_entries(backend, path, &mut entries)?;
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Keeps the `entries` that are not encrypted for exactly `recipients`, in order.
+///
+/// See [`stale_entries`].
+fn filter_stale(
+    backend: &dyn CryptoBackend,
+    entries: Vec<PathBuf>,
+    recipients: &[String],
+) -> Result<Vec<PathBuf>, StoreError> {
+    let Some(expected) = backend.recipient_key_ids(recipients)? else {
+        return Ok(entries);
+    };
+    let key_ids = parallel_map(&entries, |entry| backend.encrypted_key_ids(entry));
+
+    let mut stale = Vec::new();
+    for (entry, key_ids) in entries.into_iter().zip(key_ids) {
+        if key_ids?.as_ref() != Some(&expected) {
+            stale.push(entry);
+        }
+    }
+    Ok(stale)
+}
+
+/// Recursively collects the entries under `dir` that share its recipients.
+///
+/// Subfolders with their own recipients file and hidden directories such as `.git` are skipped.
+fn collect_entries(
+    backend: &dyn CryptoBackend,
+    dir: &Path,
+    entries: &mut Vec<PathBuf>,
+) -> Result<(), StoreError> {
+    let dir_entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(StoreError::io(
+                format!("Failed to read directory {}", dir.display()),
+                e,
+            ))
+        }
+    };
+
+    for entry in dir_entries {
+        let entry = match entry {
+            Ok(ent) => ent,
+            Err(e) => return Err(StoreError::io("Failed to read directory entry", e)),
+        };
+        let path = entry.path();
+
+        if path.is_dir() {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && !path.join(backend.recipients_file()).is_file() {
+                collect_entries(backend, &path, entries)?;
+            }
+        } else if let Some(ext) = path.extension() {
+            if ext == backend.extension() {
+                entries.push(path);
             }
         }
-        Ok(())
     }
+    Ok(())
+}
 
-    reencrypt_dir(root, &recipient)
+/// Re-encrypts the entry at `path` for `recipients` without ever leaving it half-written.
+///
+/// The entry is decrypted and encrypted again into a hidden file next to it (see
+/// [`temp_path`]). That file is decrypted once more to check that it round-trips, synced to
+/// disk, and then renamed over the entry, so a crash or a failing backend leaves either the
+/// original or the new file in place.
+///
+/// Because of the check, one of `recipients` must be a key the backend can decrypt with.
+///
+/// # Returns
+///
+/// * `Ok(())` if the entry was replaced.
+/// * `Err(StoreError)` if decryption, encryption or the check fails, or the file cannot be
+///   synced or renamed. The original entry is left untouched.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::path::Path;
+/// # use password_store_rs::integrations::gpg::{reencrypt_file, GpgBackend};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// reencrypt_file(
+///     &GpgBackend::default(),
+///     Path::new("/path/to/store/example.com.gpg"),
+///     &["alice@example.com".to_string()],
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn reencrypt_file(
+    backend: &dyn CryptoBackend,
+    path: &Path,
+    recipients: &[String],
+) -> Result<(), StoreError> {
+    let temp = encrypt_to_temp(backend, path, recipients)?;
+    rename_synced(&temp, path)
+}
+
+/// Decrypts `entry`, encrypts it for `recipients` into [`temp_path`], checks that the result
+/// decrypts to the same contents, syncs it, and returns its path.
+///
+/// This is the first half of [`reencrypt_file`]: the entry itself is left untouched, so the
+/// caller can re-encrypt several entries and only rename the temporary files over them (see
+/// [`rename_synced`]) once every one has succeeded.
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` with the path of the synced temporary file.
+/// * `Err(StoreError)` if decryption, encryption or the check fails. The temporary file is
+///   removed again.
+pub fn encrypt_to_temp(
+    backend: &dyn CryptoBackend,
+    entry: &Path,
+    recipients: &[String],
+) -> Result<PathBuf, StoreError> {
+    let contents = backend.decrypt(entry)?;
+    let temp = temp_path(entry);
+
+    let written = backend.encrypt(&contents, recipients, &temp).and_then(|_| {
+        if backend.decrypt(&temp)? != contents {
+            return Err(StoreError::backend(
+                backend.name(),
+                format!("Re-encrypted {} does not decrypt back", entry.display()),
+                "",
+            ));
+        }
+        sync_file(&temp)
+    });
+
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(temp)
 }
 
 /// Returns a set of all GPG key fingerprints currently available in the keyring.
@@ -256,35 +936,31 @@ pub fn reencrypt_path(path: &str) -> Result<(), String> {
 ///
 /// # Returns
 ///
-/// A `HashSet<String>` containing the fingerprints of all public keys (both
-/// primary and subkeys) found in the GPG keyring.
-///
-/// # Panics
-///
-/// This function will panic if the `gpg` command fails to execute successfully.
+/// * `Ok(HashSet<String>)` containing the fingerprints of all public keys (both
+///   primary and subkeys) found in the GPG keyring.
+/// * `Err(StoreError::GpgFailed)` if the `gpg` command fails to execute successfully.
 ///
 /// # Notes
 ///
 /// - Fingerprints are returned as-is in the order parsed from the output.
 /// - Duplicate entries are automatically de-duplicated by the `HashSet`.
-/// - If you want only primary key fingerprints, use [`get_primary_fingerprint`] instead.
+/// - If you want only primary key fingerprints, use [`CryptoBackend::list_keys`] instead.
 ///
 /// # Example
 ///
-/// ```rust
-/// let fingerprints = list_key_fingerprints();
+/// ```rust,no_run
+/// # use password_store_rs::integrations::gpg::list_key_fingerprints;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let fingerprints = list_key_fingerprints()?;
 /// for fpr in &fingerprints {
-///     println!(\"Key: {}\", fpr);
+///     println!("Key: {}", fpr);
 /// }
+/// # Ok(())
+/// # }
 /// ```
-pub fn list_key_fingerprints() -> HashSet<String> {
-    let output = Command::new("gpg")
-        .args(["--list-keys", "--with-colons"])
-        .output()
-        .expect("Failed to list GPG keys");
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    stdout
+pub fn list_key_fingerprints() -> Result<HashSet<String>, StoreError> {
+    let stdout = GpgBackend::default().list_keys_with_colons()?;
+    Ok(stdout
         .lines()
         .filter_map(|line| {
             if line.starts_with("fpr:") {
@@ -293,108 +969,335 @@ pub fn list_key_fingerprints() -> HashSet<String> {
                 None
             }
         })
-        .collect()
+        .collect())
 }
 
-/// Returns the fingerprint of the most recently listed primary GPG key (if any).
-///
-/// This function parses the output of `gpg --list-keys --with-colons` to find the
-/// fingerprint (`fpr`) line immediately following a `pub` (public key) line.
-/// It assumes that the GPG keyring is in a consistent format and that the fingerprint
-/// for each primary key directly follows its `pub:` entry.
-///
-/// # Returns
-///
-/// - `Some(String)` containing the fingerprint of the first encountered primary key.
-/// - `None` if no valid primary key fingerprint can be found.
+/// Returns the GPG executable to run.
 ///
-/// # Panics
-///
-/// This function panics if the `gpg` command fails to execute.
-///
-/// # Notes
+/// This is `$GPG` if it is set, otherwise `gpg2` when it is found in `$PATH`, and `gpg` as a
+/// last resort, like pass.
+pub fn gpg_executable() -> String {
+    match env::var("GPG") {
+        Ok(gpg) if !gpg.trim().is_empty() => gpg,
+        _ if executable_in_path("gpg2") => "gpg2".to_string(),
+        _ => "gpg".to_string(),
+    }
+}
+
+/// Returns the options that every GPG invocation starts with.
 ///
-/// - This only returns the **first** primary key found in the output.
-/// - It ignores subkey (`sub:`) entries and their fingerprints.
-/// - Suitable for use cases where a single default or most recently generated key is expected.
+/// These are the whitespace-separated options in `PASSWORD_STORE_GPG_OPTS` and `GPG_OPTS`,
+/// followed by the defaults of pass: `--quiet --yes --compress-algo=none --no-encrypt-to`.
 ///
-/// # Example
+/// # Examples
 ///
 /// ```rust
-/// if let Some(fpr) = get_primary_fingerprint() {
-///     println!(\"Primary key fingerprint: {}\", fpr);
-/// } else {
-///     println!(\"No primary GPG key found.\");
-/// }
+/// # use password_store_rs::integrations::gpg::gpg_options;
+/// # std::env::set_var("PASSWORD_STORE_GPG_OPTS", "--trust-model always");
+/// # std::env::remove_var("GPG_OPTS");
+/// // PASSWORD_STORE_GPG_OPTS="--trust-model always"
+/// assert_eq!(
+///     gpg_options(),
+///     ["--trust-model", "always", "--quiet", "--yes", "--compress-algo=none", "--no-encrypt-to"],
+/// );
 /// ```
-pub fn get_primary_fingerprint() -> Option<String> {
-    let output = Command::new("gpg")
-        .args(["--list-keys", "--with-colons"])
-        .output()
-        .expect("Failed to list GPG keys");
+pub fn gpg_options() -> Vec<String> {
+    let mut options: Vec<String> = ["PASSWORD_STORE_GPG_OPTS", "GPG_OPTS"]
+        .iter()
+        .flat_map(|name| {
+            env::var(name)
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    options.extend(
+        [
+            "--quiet",
+            "--yes",
+            "--compress-algo=none",
+            "--no-encrypt-to",
+        ]
+        .map(str::to_string),
+    );
+    options
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut in_pub = false;
+impl GpgBackend {
+    /// Runs `gpg --list-keys --with-colons` and returns its standard output.
+    fn list_keys_with_colons(&self) -> Result<String, StoreError> {
+        let output = self
+            .status_command()
+            .args(["--list-keys", "--with-colons"])
+            .output()
+            .map_err(|e| gpg_not_run("Failed to list GPG keys", e))?;
 
-    for line in stdout.lines() {
-        if line.starts_with("pub:") {
-            in_pub = true;
-        } else if in_pub && line.starts_with("fpr:") {
-            return line.split(':').nth(9).map(|s| s.to_string());
+        if !output.status.success() {
+            return Err(gpg_failed("Failed to list GPG keys", &output));
         }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 
-    None
+    /// Returns the key IDs of the secret keys and subkeys in the keyring, upper-case.
+    fn secret_key_ids(&self) -> Result<HashSet<String>, StoreError> {
+        let output = self
+            .status_command()
+            .args(["--list-secret-keys", "--with-colons"])
+            .output()
+            .map_err(|e| gpg_not_run("Failed to list GPG secret keys", e))?;
+
+        if !output.status.success() {
+            return Err(gpg_failed("Failed to list GPG secret keys", &output));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.starts_with("sec:") || line.starts_with("ssb:"))
+            .filter_map(|line| line.split(':').nth(4))
+            .map(str::to_uppercase)
+            .collect())
+    }
 }
 
-/// Generates a new GPG key using the interactive `gpg --full-gen-key` command
-/// and returns the fingerprint of the newly created **primary** key.
+/// Returns the error for a GPG process that could not be started at all.
+fn gpg_not_run(context: &str, e: std::io::Error) -> StoreError {
+    StoreError::GpgFailed {
+        context: context.to_string(),
+        status: None,
+        stderr: e.to_string(),
+        problem: None,
+    }
+}
+
+/// Picks the encryption key of every key in `gpg --list-keys --with-colons` output.
 ///
-/// This function invokes GPG in interactive mode to create a new key pair.
-/// After generation, it retrieves and returns the fingerprint of the primary
-/// key (not the subkey), which is typically used for signing and identifying
-/// the key.
+/// For every key, the newest valid (not invalid, disabled, revoked or expired) subkey, or
+/// primary key, whose capabilities include `e` is taken, which is the one `gpg --encrypt`
+/// picks. A key that is listed more than once, e.g. because a `.gpg-id` names it by both
+/// email and key ID, counts once: keys are told apart by the fingerprint of the primary key.
 ///
 /// # Returns
 ///
-/// A `String` representing the fingerprint of the newly generated primary key.
-///
-/// # Panics
-///
-/// This function will terminate the program (`exit(1)`) if:
-/// - The `gpg` command fails to execute.
-/// - The user cancels or fails to complete the key generation.
-/// - The primary key fingerprint cannot be extracted after key creation.
-///
-/// # Notes
-///
-/// - The GPG interface is interactive and requires user input.
-/// - It is assumed that the key created is the most recent one in the keyring.
-///
-/// # Example
-///
-/// ```rust
-/// let new_fpr = generate_new_gpg_key();
-/// println!(\"New GPG key fingerprint: {}\", new_fpr);
-/// ```
-pub fn generate_new_gpg_key() -> String {
-    let status = Command::new("gpg")
-        .arg("--full-gen-key")
-        .status()
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to execute gpg --full-gen-key: {}", e);
-            exit(1);
-        });
-
-    if !status.success() {
-        eprintln!("Failed to generate a new GPG key.");
-        exit(1);
-    }
-
-    let fingerprint = get_primary_fingerprint().unwrap_or_else(|| {
-        eprintln!("Failed to extract primary fingerprint from generated key.");
-        exit(1);
-    });
+/// The key IDs of the encryption keys, or `None` if a key has no usable encryption key.
+fn encryption_key_ids(list_keys: &str) -> Option<HashSet<String>> {
+    // The newest encryption key (creation time, key ID) of each key, by primary fingerprint.
+    let mut keys = Vec::new();
+    for line in list_keys.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        let record = fields.first().copied().unwrap_or("");
+        if record == "pub" {
+            keys.push((None, None));
+        }
+        let Some((fingerprint, newest)) = keys.last_mut() else {
+            continue;
+        };
+        if record == "fpr" && fingerprint.is_none() {
+            *fingerprint = fields.get(9).map(|fpr| fpr.to_uppercase());
+        }
+        if record != "pub" && record != "sub" {
+            continue;
+        }
 
-    fingerprint
+        let validity = fields.get(1).copied().unwrap_or("");
+        let capabilities = fields.get(11).copied().unwrap_or("");
+        if ["i", "d", "r", "e", "n"].contains(&validity) || !capabilities.contains('e') {
+            continue;
+        }
+        let created: u64 = fields.get(5).and_then(|c| c.parse().ok()).unwrap_or(0);
+        if newest.as_ref().is_none_or(|(time, _)| created >= *time) {
+            let key_id = fields.get(4).copied().unwrap_or("").to_uppercase();
+            *newest = Some((created, key_id));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut key_ids = HashSet::new();
+    for (fingerprint, newest) in keys {
+        if fingerprint.is_some_and(|fingerprint| !seen.insert(fingerprint)) {
+            continue;
+        }
+        key_ids.insert(newest?.1);
+    }
+    Some(key_ids)
+}
+
+/// What GPG reported on its `[GNUPG:]` status lines (see [`GpgBackend::status_command`]).
+#[derive(Debug, Default)]
+struct GpgStatus {
+    /// The key IDs of the `ENC_TO` lines: the keys a file is encrypted to.
+    encrypted_to: Vec<String>,
+    /// The key IDs of the `NO_SECKEY` lines.
+    no_secret_key: Vec<String>,
+    /// The signing and primary key fingerprints of the `VALIDSIG` lines.
+    valid_signatures: HashSet<String>,
+    /// The fingerprints of the `KEY_CONSIDERED` lines.
+    considered: Vec<String>,
+    /// The reason codes and recipients of the `INV_RECP` lines.
+    invalid_recipients: Vec<(u32, String)>,
+    /// Whether there was a `DECRYPTION_FAILED` line.
+    decryption_failed: bool,
+    /// Whether there was a `PINENTRY_LAUNCHED` line.
+    pinentry_launched: bool,
+    /// Whether there was a `KEYEXPIRED` line.
+    key_expired: bool,
+    /// Whether there was a `NODATA` line.
+    no_data: bool,
+}
+
+impl GpgStatus {
+    /// Reads the status lines from GPG's standard error. Other lines are ignored.
+    fn parse(stderr: &[u8]) -> GpgStatus {
+        let mut status = GpgStatus::default();
+        for line in String::from_utf8_lossy(stderr).lines() {
+            let Some(rest) = line.strip_prefix("[GNUPG:] ") else {
+                continue;
+            };
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let first = fields.get(1).map(|field| field.to_uppercase());
+            match fields.first().copied().unwrap_or("") {
+                "ENC_TO" => status.encrypted_to.extend(first),
+                "NO_SECKEY" => status.no_secret_key.extend(first),
+                "VALIDSIG" => {
+                    // `VALIDSIG <fingerprint> <date> <timestamp> ... <primary-key-fingerprint>`
+                    status.valid_signatures.extend(first);
+                    if let Some(primary) = fields.last().filter(|_| fields.len() > 2) {
+                        status.valid_signatures.insert(primary.to_uppercase());
+                    }
+                }
+                "KEY_CONSIDERED" => status.considered.extend(first),
+                "INV_RECP" => {
+                    let reason = fields.get(1).and_then(|r| r.parse().ok()).unwrap_or(0);
+                    let recipient = fields.get(2..).unwrap_or_default().join(" ");
+                    status.invalid_recipients.push((reason, recipient));
+                }
+                "DECRYPTION_FAILED" => status.decryption_failed = true,
+                "PINENTRY_LAUNCHED" => status.pinentry_launched = true,
+                "KEYEXPIRED" => status.key_expired = true,
+                "NODATA" => status.no_data = true,
+                _ => {}
+            }
+        }
+        status
+    }
+
+    /// Returns the most likely cause of the failed operation that printed these lines.
+    fn problem(&self) -> Option<GpgProblem> {
+        if let Some((reason, recipient)) = self.invalid_recipients.first() {
+            // An expired key is reported as `KEYEXPIRED` followed by `INV_RECP 0`.
+            if *reason == 0 && self.key_expired {
+                return Some(GpgProblem::KeyExpired(self.considered.last().cloned()));
+            }
+            return Some(GpgProblem::InvalidRecipient {
+                recipient: recipient.clone(),
+                reason: *reason,
+            });
+        }
+        if self.no_data {
+            return Some(GpgProblem::NoData);
+        }
+        if self.decryption_failed {
+            if self.pinentry_launched {
+                return Some(GpgProblem::PassphraseNotEntered);
+            }
+            if !self.no_secret_key.is_empty() {
+                return Some(GpgProblem::NoSecretKey(self.no_secret_key.clone()));
+            }
+            return Some(GpgProblem::DecryptionFailed);
+        }
+        if self.key_expired {
+            return Some(GpgProblem::KeyExpired(self.considered.last().cloned()));
+        }
+        None
+    }
+
+    /// Returns the error for the failed GPG process `output` that printed these lines.
+    ///
+    /// The status lines are left out of the error's `stderr`, and [`GpgStatus::problem`]
+    /// becomes its [`GpgProblem`].
+    fn error(&self, context: impl Into<String>, output: &Output) -> StoreError {
+        let stderr: Vec<String> = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .filter(|line| !line.starts_with("[GNUPG:] "))
+            .map(str::to_string)
+            .collect();
+        StoreError::GpgFailed {
+            context: context.into(),
+            status: output.status.code(),
+            stderr: stderr.join("\n").trim().to_string(),
+            problem: self.problem(),
+        }
+    }
+}
+
+/// Returns the error for a GPG process that ran and failed, explained by its status lines.
+fn gpg_failed(context: impl Into<String>, output: &Output) -> StoreError {
+    GpgStatus::parse(&output.stderr).error(context, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `gpg --list-keys --with-colons` output for a key with an expired and a current
+    /// encryption subkey, followed by a second key.
+    const LIST_KEYS: &str = "\
+tru::1:1792176781:0:3:1:5
+pub:u:3072:1:F32DA6600FE8EC4F:1792176779:::u:::scESC::::::23::0:
+fpr:::::::::3081602D4A31F9772DAF26AAF32DA6600FE8EC4F:
+uid:u::::1792176779::7AD0C2E655786217C70E9384866F23969DDAD952::Test One <one@example.com>::::::::::0:
+sub:e:3072:1:1111111111111111:1692176779::::::e::::::23:
+fpr:::::::::AAAAAAAAAAAAAAAAAAAAAAAA1111111111111111:
+sub:u:3072:1:87d43b2df1981c95:1792176779::::::e::::::23:
+fpr:::::::::121F94BBD949A7F21EEACFE187D43B2DF1981C95:
+pub:u:255:22:46952629F3D63D29:1792176790:::u:::scESC::::::23::0:
+fpr:::::::::6A1B4C70FE6E2F7A0D6E0E4546952629F3D63D29:
+uid:u::::1792176790::1E07E0A7A2C7F6E2C1B4D4E3C2B1A09F8E7D6C5B::Test Two <two@example.com>::::::::::0:
+sub:u:255:18:0F0E0D0C0B0A0908:1792176790::::::e::::::23:
+fpr:::::::::BBBBBBBBBBBBBBBBBBBBBBBB0F0E0D0C0B0A0908:
+";
+
+    /// The first key of [`LIST_KEYS`], without its `tru` line.
+    fn first_key() -> &'static str {
+        let start = LIST_KEYS.find("pub:").unwrap();
+        let end = LIST_KEYS[start + 1..].find("pub:").unwrap() + start + 1;
+        &LIST_KEYS[start..end]
+    }
+
+    fn key_ids(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn picks_the_newest_valid_encryption_key_of_every_key() {
+        assert_eq!(
+            encryption_key_ids(LIST_KEYS),
+            Some(key_ids(&["87D43B2DF1981C95", "0F0E0D0C0B0A0908"]))
+        );
+    }
+
+    #[test]
+    fn a_key_listed_twice_counts_once() {
+        // What a `.gpg-id` naming the same key by email and by key ID can list.
+        let twice = format!("{}{}", first_key(), LIST_KEYS);
+        assert_eq!(
+            encryption_key_ids(&twice),
+            Some(key_ids(&["87D43B2DF1981C95", "0F0E0D0C0B0A0908"]))
+        );
+    }
+
+    #[test]
+    fn a_key_without_an_encryption_key_is_unusable() {
+        let sign_only = "\
+pub:u:255:22:46952629F3D63D29:1792176790:::u:::scSC::::::23::0:
+fpr:::::::::6A1B4C70FE6E2F7A0D6E0E4546952629F3D63D29:
+sub:r:255:18:0F0E0D0C0B0A0908:1792176790::::::e::::::23:
+";
+        assert_eq!(
+            encryption_key_ids(&format!("{}{}", first_key(), sign_only)),
+            None
+        );
+        assert_eq!(encryption_key_ids(""), Some(HashSet::new()));
+    }
 }
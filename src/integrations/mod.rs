@@ -1,2 +1,6 @@
+pub mod age;
+pub mod clipboard;
 pub mod git;
 pub mod gpg;
+#[cfg(feature = "openpgp")]
+pub mod openpgp;
@@ -0,0 +1,698 @@
+use crate::crypto::CryptoBackend;
+use crate::error::StoreError;
+use pgp::composed::{
+    Deserializable, DetachedSignature, Esk, Message, MessageBuilder, PublicOrSecret,
+    SignedPublicKey, SignedPublicSubKey, SignedSecretKey,
+};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::packet::{Signature, SignatureType};
+use pgp::ser::Serialize;
+use pgp::types::{KeyDetails, Password, Tag, Timestamp};
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A [`CryptoBackend`] that encrypts and decrypts in-process with the pure-Rust `pgp` crate.
+///
+/// Keys are read once, when the backend is created, from the files listed in
+/// `PASSWORD_STORE_PGP_KEYRING` (separated like `$PATH`). Each file may hold any mix of
+/// public and secret keys, armored or binary, as written by
+/// `gpg --export-secret-keys > keyring.pgp` or `gpg --export --armor > public.asc`.
+///
+/// Files are written in the same format as `gpg --encrypt`: a binary OpenPGP message with
+/// one public-key encrypted session key per recipient and an AES-256 SEIPD (v1) packet, so
+/// entries written by either backend can be read by the other. Recipients are matched the
+/// way `.gpg-id` files are usually written: by fingerprint or key ID (with or without `0x`),
+/// or by a case-insensitive substring of a user ID such as an email address. Like gpg, a
+/// recipient's key is only used while it is neither revoked nor expired, and the newest valid
+/// encryption subkey is encrypted to, so both backends encrypt to the same keys.
+///
+/// Protected secret keys are unlocked with `PASSWORD_STORE_PGP_PASSPHRASE` if it is set, or
+/// with a passphrase prompted for once and remembered for the rest of the process.
+///
+/// This backend is only available with the `openpgp` cargo feature, and is selected with
+/// `PASSWORD_STORE_CRYPTO_BACKEND=openpgp` (see [`crate::crypto::default_backend`]).
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::integrations::openpgp::OpenPgpBackend;
+/// # use password_store_rs::PasswordStore;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// // PASSWORD_STORE_PGP_KEYRING=/home/me/.config/pass/keyring.pgp
+/// let backend = OpenPgpBackend::from_env()?;
+/// let store = PasswordStore::with_backend("/home/me/.password-store", Box::new(backend))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct OpenPgpBackend {
+    /// Every certificate in the keyring, including the public part of each secret key.
+    certs: Vec<SignedPublicKey>,
+    /// The secret keys in the keyring.
+    secrets: Vec<SignedSecretKey>,
+    /// The passphrase for protected secret keys, once it is known.
+    passphrase: Mutex<Option<String>>,
+}
+
+impl OpenPgpBackend {
+    /// Creates a backend from the keyring files listed in `PASSWORD_STORE_PGP_KEYRING`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OpenPgpBackend)` with the keys of every listed file.
+    /// * `Err(StoreError::InvalidInput)` if `PASSWORD_STORE_PGP_KEYRING` is not set.
+    /// * `Err(StoreError)` if a keyring file cannot be read or parsed (see [`OpenPgpBackend::from_files`]).
+    pub fn from_env() -> Result<OpenPgpBackend, StoreError> {
+        let keyring = env::var_os("PASSWORD_STORE_PGP_KEYRING")
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| {
+                StoreError::InvalidInput(
+                    "PASSWORD_STORE_PGP_KEYRING must name the exported keyring to use, \
+                     e.g. from \"gpg --export-secret-keys > keyring.pgp\"."
+                        .to_string(),
+                )
+            })?;
+        let files: Vec<PathBuf> = env::split_paths(&keyring).collect();
+        let backend = OpenPgpBackend::from_files(&files)?;
+        if let Ok(passphrase) = env::var("PASSWORD_STORE_PGP_PASSPHRASE") {
+            *backend.passphrase.lock().unwrap() = Some(passphrase);
+        }
+        Ok(backend)
+    }
+
+    /// Creates a backend from the public and secret keys in `files`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OpenPgpBackend)` with the keys of every file. Keys listed more than once are
+    ///   kept once.
+    /// * `Err(StoreError::Io)` if a file cannot be opened.
+    /// * `Err(StoreError::CryptoFailed)` if a file does not contain valid OpenPGP keys.
+    pub fn from_files(files: &[PathBuf]) -> Result<OpenPgpBackend, StoreError> {
+        let mut certs: Vec<SignedPublicKey> = Vec::new();
+        let mut secrets: Vec<SignedSecretKey> = Vec::new();
+
+        for file in files {
+            let reader = File::open(file)
+                .map_err(|e| StoreError::io(format!("Failed to open {}", file.display()), e))?;
+            let context = format!("Failed to read OpenPGP keys from {}", file.display());
+            let (keys, _) = PublicOrSecret::from_reader_many(reader)
+                .map_err(|e| openpgp_failed(&context, e))?;
+
+            for key in keys {
+                let key = key.map_err(|e| openpgp_failed(&context, e))?;
+                let cert = match key {
+                    PublicOrSecret::Public(cert) => cert,
+                    PublicOrSecret::Secret(secret) => {
+                        let cert = secret.to_public_key();
+                        if !secrets
+                            .iter()
+                            .any(|known| known.fingerprint() == secret.fingerprint())
+                        {
+                            secrets.push(secret);
+                        }
+                        cert
+                    }
+                };
+                if !certs
+                    .iter()
+                    .any(|known| known.fingerprint() == cert.fingerprint())
+                {
+                    certs.push(cert);
+                }
+            }
+        }
+
+        Ok(OpenPgpBackend {
+            certs,
+            secrets,
+            passphrase: Mutex::new(None),
+        })
+    }
+
+    /// Returns the first certificate in the keyring that matches `key_id`.
+    fn find_cert(&self, key_id: &str) -> Option<&SignedPublicKey> {
+        self.certs.iter().find(|cert| cert_matches(cert, key_id))
+    }
+
+    /// Runs `operation` with the passphrase for protected secret keys.
+    ///
+    /// When no passphrase is known yet, `operation` is first tried with an empty one, since
+    /// the key it needs may not be protected. If that fails, the user is prompted once.
+    fn with_passphrase<T>(
+        &self,
+        operation: impl Fn(&Password) -> pgp::errors::Result<T>,
+    ) -> Result<pgp::errors::Result<T>, StoreError> {
+        let known = self.passphrase.lock().unwrap().clone();
+        if let Some(passphrase) = known {
+            return Ok(operation(&Password::from(passphrase.as_str())));
+        }
+
+        let result = operation(&Password::empty());
+        if result.is_ok() || !self.has_protected_keys() {
+            return Ok(result);
+        }
+
+        // Hold the lock while prompting, so parallel decryptions ask only once.
+        let mut slot = self.passphrase.lock().unwrap();
+        if slot.is_none() {
+            let entered = rpassword::prompt_password("Passphrase for the OpenPGP secret key: ")
+                .map_err(|e| StoreError::io("Failed to read passphrase", e))?;
+            *slot = Some(entered);
+        }
+        let passphrase = slot.clone().unwrap_or_default();
+        drop(slot);
+
+        Ok(operation(&Password::from(passphrase.as_str())))
+    }
+
+    /// Returns whether any secret key in the keyring is protected by a passphrase.
+    fn has_protected_keys(&self) -> bool {
+        self.secrets.iter().any(|secret| {
+            secret.primary_key.secret_params().is_encrypted()
+                || secret
+                    .secret_subkeys
+                    .iter()
+                    .any(|sub| sub.key.secret_params().is_encrypted())
+        })
+    }
+}
+
+impl CryptoBackend for OpenPgpBackend {
+    /// Encrypts `contents` to the encryption subkey of every recipient and writes the binary
+    /// message to `output`, replacing any existing file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the file was written successfully.
+    /// * `Err(StoreError::RecipientMissing)` if a recipient has no usable key in the keyring,
+    ///   e.g. because it is revoked or expired.
+    /// * `Err(StoreError::CryptoFailed)` if encryption fails.
+    /// * `Err(StoreError::Io)` if the file cannot be written.
+    fn encrypt(
+        &self,
+        contents: &[u8],
+        recipients: &[String],
+        output: &Path,
+    ) -> Result<(), StoreError> {
+        let mut rng = rand::thread_rng();
+        let mut builder = MessageBuilder::from_bytes("", contents.to_vec())
+            .seipd_v1(&mut rng, SymmetricKeyAlgorithm::AES256);
+
+        // A key named twice, e.g. by email and by key ID, is encrypted to once.
+        let mut added_certs = HashSet::new();
+        for recipient in recipients {
+            let cert = self.find_cert(recipient).ok_or_else(|| {
+                StoreError::RecipientMissing(format!(
+                    "No public key for recipient '{}' in the OpenPGP keyring.",
+                    recipient
+                ))
+            })?;
+            if !added_certs.insert(cert.fingerprint()) {
+                continue;
+            }
+            let context = format!("OpenPGP encryption failed for {}", output.display());
+            let added = match recipient_key(cert, Timestamp::now()) {
+                Ok(RecipientKey::Subkey(subkey)) => {
+                    builder.encrypt_to_key(&mut rng, subkey).map(|_| ())
+                }
+                Ok(RecipientKey::Primary(cert)) => {
+                    builder.encrypt_to_key(&mut rng, cert).map(|_| ())
+                }
+                Err(reason) => {
+                    return Err(StoreError::RecipientMissing(format!(
+                        "The key for recipient '{}' {}.",
+                        recipient, reason
+                    )))
+                }
+            };
+            added.map_err(|e| openpgp_failed(&context, e))?;
+        }
+
+        let encrypted = builder.to_vec(&mut rng).map_err(|e| {
+            openpgp_failed(
+                &format!("OpenPGP encryption failed for {}", output.display()),
+                e,
+            )
+        })?;
+        fs::write(output, encrypted)
+            .map_err(|e| StoreError::io(format!("Failed to write {}", output.display()), e))
+    }
+
+    /// Decrypts the OpenPGP message at `path` with the secret keys in the keyring.
+    ///
+    /// Armored and binary messages are accepted, and compressed data is decompressed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<u8>)` with the decrypted bytes.
+    /// * `Err(StoreError::CryptoFailed)` if no secret key can decrypt the message.
+    /// * `Err(StoreError::Io)` if the file cannot be read.
+    fn decrypt(&self, path: &Path) -> Result<Vec<u8>, StoreError> {
+        let ciphertext = fs::read(path)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+        let keys: Vec<&SignedSecretKey> = self.secrets.iter().collect();
+
+        let result = self.with_passphrase(|password| {
+            let (message, _) = Message::from_reader(&ciphertext[..])?;
+            let mut message =
+                message.decrypt_with_keys(vec![password; keys.len()], keys.clone())?;
+            if message.is_compressed() {
+                message = message.decompress()?;
+            }
+            Ok(message.as_data_vec()?)
+        })?;
+
+        result.map_err(|e| {
+            openpgp_failed(
+                &format!("OpenPGP decryption failed for {}", path.display()),
+                e,
+            )
+        })
+    }
+
+    /// Writes a binary detached signature of `path` to `<path>.sig`.
+    ///
+    /// The primary key of the first secret key matching one of `signing_keys` signs, or of
+    /// the first secret key in the keyring when `signing_keys` is empty.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the signature was written.
+    /// * `Err(StoreError::RecipientMissing)` if no matching secret key is in the keyring.
+    /// * `Err(StoreError::CryptoFailed)` if signing fails.
+    /// * `Err(StoreError::Io)` if a file cannot be read or written.
+    fn sign(&self, path: &Path, signing_keys: &[String]) -> Result<(), StoreError> {
+        let secret = self
+            .secrets
+            .iter()
+            .find(|secret| {
+                let cert = secret.to_public_key();
+                signing_keys.is_empty() || signing_keys.iter().any(|key| cert_matches(&cert, key))
+            })
+            .ok_or_else(|| {
+                StoreError::RecipientMissing(format!(
+                    "No secret key for {} in the OpenPGP keyring.",
+                    signing_keys.join(", ")
+                ))
+            })?;
+        let data = fs::read(path)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+
+        let signature = self
+            .with_passphrase(|password| {
+                DetachedSignature::sign_binary_data(
+                    rand::thread_rng(),
+                    &secret.primary_key,
+                    password,
+                    HashAlgorithm::Sha256,
+                    &data[..],
+                )
+            })?
+            .and_then(|signature| signature.to_bytes())
+            .map_err(|e| {
+                openpgp_failed(
+                    &format!("OpenPGP failed when signing file {}", path.display()),
+                    e,
+                )
+            })?;
+
+        let mut sig_path = path.as_os_str().to_owned();
+        sig_path.push(".sig");
+        let sig_path = PathBuf::from(sig_path);
+        fs::write(&sig_path, signature)
+            .map_err(|e| StoreError::io(format!("Failed to write {}", sig_path.display()), e))
+    }
+
+    /// Checks the detached `signature` of `path` against every certificate in the keyring.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(HashSet<String>)` with the fingerprints of the signing key and its primary key,
+    ///   empty if the signature cannot be parsed or no certificate verifies it.
+    /// * `Err(StoreError::Io)` if a file cannot be read.
+    fn verify(&self, path: &Path, signature: &Path) -> Result<HashSet<String>, StoreError> {
+        let data = fs::read(path)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+        let signature_bytes = fs::read(signature)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", signature.display()), e))?;
+
+        let mut fingerprints = HashSet::new();
+        let Ok((signature, _)) = DetachedSignature::from_reader_single(&signature_bytes[..]) else {
+            return Ok(fingerprints);
+        };
+
+        for cert in &self.certs {
+            let primary = format!("{:X}", cert.fingerprint());
+            if signature.verify(cert, &data).is_ok() {
+                fingerprints.insert(primary.clone());
+            }
+            for sub in &cert.public_subkeys {
+                if signature.verify(sub, &data).is_ok() {
+                    fingerprints.insert(format!("{:X}", sub.fingerprint()));
+                    fingerprints.insert(primary.clone());
+                }
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    /// Returns the primary key fingerprints of the certificates in the keyring.
+    fn list_keys(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self
+            .certs
+            .iter()
+            .map(|cert| format!("{:X}", cert.fingerprint()))
+            .collect())
+    }
+
+    /// Returns whether a certificate in the keyring matches `key_id`.
+    fn key_exists(&self, key_id: &str) -> Result<bool, StoreError> {
+        Ok(self.find_cert(key_id).is_some())
+    }
+
+    /// Returns the recipient key IDs of the public-key encrypted session key packets in the
+    /// message at `path`, or `Ok(None)` if it is not an encrypted OpenPGP message.
+    fn encrypted_key_ids(&self, path: &Path) -> Result<Option<HashSet<String>>, StoreError> {
+        let ciphertext = fs::read(path)
+            .map_err(|e| StoreError::io(format!("Failed to read {}", path.display()), e))?;
+        let Ok((Message::Encrypted { esk, .. }, _)) = Message::from_reader(&ciphertext[..]) else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            esk.iter()
+                .filter_map(|esk| match esk {
+                    Esk::PublicKeyEncryptedSessionKey(pkesk) => pkesk.id().ok(),
+                    Esk::SymKeyEncryptedSessionKey(_) => None,
+                })
+                .map(|key_id| key_id.to_string().to_uppercase())
+                .collect(),
+        ))
+    }
+
+    /// Returns the key IDs [`CryptoBackend::encrypt`] would encrypt to for `recipients`, or
+    /// `Ok(None)` if a recipient has no usable key in the keyring. Recipients that name the
+    /// same key give one key ID.
+    fn recipient_key_ids(
+        &self,
+        recipients: &[String],
+    ) -> Result<Option<HashSet<String>>, StoreError> {
+        let mut key_ids = HashSet::new();
+        for recipient in recipients {
+            let Some(cert) = self.find_cert(recipient) else {
+                return Ok(None);
+            };
+            let key_id = match recipient_key(cert, Timestamp::now()) {
+                Ok(RecipientKey::Subkey(subkey)) => subkey.legacy_key_id(),
+                Ok(RecipientKey::Primary(cert)) => cert.legacy_key_id(),
+                Err(_) => return Ok(None),
+            };
+            key_ids.insert(key_id.to_string().to_uppercase());
+        }
+        Ok(Some(key_ids))
+    }
+
+    /// Returns `openpgp`.
+    fn name(&self) -> &'static str {
+        "openpgp"
+    }
+}
+
+/// The key of a certificate that messages for it are encrypted to.
+enum RecipientKey<'a> {
+    /// An encryption subkey, as most certificates have.
+    Subkey(&'a SignedPublicSubKey),
+    /// The primary key of a certificate without an encryption subkey.
+    Primary(&'a SignedPublicKey),
+}
+
+/// Returns the key of `cert` that messages are encrypted to at `now`, chosen like gpg does.
+///
+/// A certificate whose primary key is revoked or expired has none. Otherwise it is the newest
+/// subkey flagged for encryption that is neither revoked nor expired, or the primary key if
+/// there is no such subkey and its algorithm can encrypt. Only self-signatures that verify
+/// with the primary key are taken into account, and a subkey's flags and expiry come from its
+/// newest binding signature.
+///
+/// # Returns
+///
+/// * `Ok(RecipientKey)` with the key to encrypt to.
+/// * `Err(&str)` with why there is none, to follow "The key for recipient 'x'".
+fn recipient_key(cert: &SignedPublicKey, now: Timestamp) -> Result<RecipientKey<'_>, &'static str> {
+    let primary = &cert.primary_key;
+    if cert
+        .details
+        .revocation_signatures
+        .iter()
+        .any(|sig| sig.verify_key(primary).is_ok())
+    {
+        return Err("is revoked");
+    }
+
+    // The expiry of the primary key is in its newest self-signature: a direct key signature
+    // or a certification of one of its user IDs.
+    let direct = cert
+        .details
+        .direct_signatures
+        .iter()
+        .filter(|sig| sig.verify_key(primary).is_ok());
+    let certifications = cert.details.users.iter().flat_map(|user| {
+        user.signatures.iter().filter(|sig| {
+            sig.is_certification()
+                && sig
+                    .verify_certification(primary, Tag::UserId, &user.id)
+                    .is_ok()
+        })
+    });
+    let newest = direct.chain(certifications).max_by_key(|sig| sig.created());
+    if is_expired(primary.created_at(), newest, now) {
+        return Err("has expired");
+    }
+
+    let subkey = cert
+        .public_subkeys
+        .iter()
+        .filter(|sub| {
+            let revoked = sub.signatures.iter().any(|sig| {
+                sig.typ() == Some(SignatureType::SubkeyRevocation)
+                    && sig.verify_subkey_binding(primary, &sub.key).is_ok()
+            });
+            let binding = sub
+                .signatures
+                .iter()
+                .filter(|sig| {
+                    sig.typ() == Some(SignatureType::SubkeyBinding)
+                        && sig.verify_subkey_binding(primary, &sub.key).is_ok()
+                })
+                .max_by_key(|sig| sig.created());
+            let Some(binding) = binding else {
+                return false;
+            };
+            let flags = binding.key_flags();
+            !revoked
+                && (flags.encrypt_comms() || flags.encrypt_storage())
+                && !is_expired(sub.key.created_at(), Some(binding), now)
+        })
+        .max_by_key(|sub| sub.key.created_at());
+
+    match subkey {
+        Some(subkey) => Ok(RecipientKey::Subkey(subkey)),
+        None if cert.algorithm().can_encrypt() => Ok(RecipientKey::Primary(cert)),
+        None => Err("has no valid encryption key"),
+    }
+}
+
+/// Returns whether a key created at `created` has expired at `now`, according to the key
+/// expiration time of its newest self-signature `signature`. A key without one never expires.
+fn is_expired(created: Timestamp, signature: Option<&Signature>, now: Timestamp) -> bool {
+    let expiration = signature
+        .and_then(|sig| sig.key_expiration_time())
+        .map_or(0, |expiration| expiration.as_secs());
+    expiration != 0
+        && u64::from(created.as_secs()) + u64::from(expiration) <= u64::from(now.as_secs())
+}
+
+/// Returns whether `cert` is the key a `.gpg-id` line such as `key_id` refers to.
+///
+/// A hexadecimal `key_id` of 8, 16 or 40 digits, optionally prefixed with `0x` or followed
+/// by `!`, matches the end of the fingerprint of the primary key or of a subkey. Anything
+/// else matches when it appears in one of the user IDs, ignoring case, so that
+/// `alice@example.com` matches `Alice <alice@example.com>`.
+fn cert_matches(cert: &SignedPublicKey, key_id: &str) -> bool {
+    let key_id = key_id.trim();
+    let hex = key_id
+        .trim_start_matches("0x")
+        .trim_start_matches("0X")
+        .trim_end_matches('!')
+        .to_uppercase();
+
+    if matches!(hex.len(), 8 | 16 | 40) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        let primary = format!("{:X}", cert.fingerprint());
+        return primary.ends_with(&hex)
+            || cert
+                .public_subkeys
+                .iter()
+                .any(|sub| format!("{:X}", sub.fingerprint()).ends_with(&hex));
+    }
+
+    let needle = key_id
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase();
+    !needle.is_empty()
+        && cert.details.users.iter().any(|user| {
+            String::from_utf8_lossy(user.id.id())
+                .to_lowercase()
+                .contains(&needle)
+        })
+}
+
+/// Returns the error for a failed operation of the `pgp` crate.
+fn openpgp_failed(context: &str, e: pgp::errors::Error) -> StoreError {
+    StoreError::backend("openpgp", context, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrations::gpg::GpgBackend;
+
+    /// Alice's secret key, exported by gpg. Her encryption subkey 6C36552B2725F07F is valid; a
+    /// newer one has expired and the newest is revoked.
+    const ALICE: &str = "alice.sec.asc";
+    /// The public keys of Bob, whose key has expired, and of Carol, whose key is revoked.
+    const EXPIRED_AND_REVOKED: &str = "expired-and-revoked.asc";
+    /// A message gpg encrypted for alice@example.com.
+    const GPG_MESSAGE: &str = "gpg-message.asc";
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/openpgp")
+            .join(name)
+    }
+
+    fn backend(files: &[&str]) -> OpenPgpBackend {
+        let files: Vec<PathBuf> = files.iter().map(|name| fixture(name)).collect();
+        OpenPgpBackend::from_files(&files).unwrap()
+    }
+
+    /// Returns a path in the temporary directory for the test `name`, with nothing at it.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "password-store-rs-openpgp-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn recipients(recipient: &str) -> Vec<String> {
+        vec![recipient.to_string()]
+    }
+
+    fn key_ids(key_ids: &[&str]) -> Option<HashSet<String>> {
+        Some(key_ids.iter().map(|key_id| key_id.to_string()).collect())
+    }
+
+    #[test]
+    fn an_entry_decrypts_to_what_was_encrypted() {
+        let backend = backend(&[ALICE]);
+        let entry = temp_path("round-trip.gpg");
+        backend
+            .encrypt(
+                b"hunter2\nuser: alice\n",
+                &recipients("alice@example.com"),
+                &entry,
+            )
+            .unwrap();
+
+        assert_eq!(backend.decrypt(&entry).unwrap(), b"hunter2\nuser: alice\n");
+        assert_eq!(
+            backend.encrypted_key_ids(&entry).unwrap(),
+            key_ids(&["6C36552B2725F07F"])
+        );
+        fs::remove_file(&entry).unwrap();
+    }
+
+    #[test]
+    fn entries_encrypted_by_gpg_decrypt() {
+        let backend = backend(&[ALICE]);
+        assert_eq!(
+            backend.decrypt(&fixture(GPG_MESSAGE)).unwrap(),
+            b"Encrypted by gpg for alice.\n"
+        );
+    }
+
+    #[test]
+    fn recipients_get_the_encryption_key_gpg_chose() {
+        let backend = backend(&[ALICE]);
+        let gpg_key_ids = backend.encrypted_key_ids(&fixture(GPG_MESSAGE)).unwrap();
+        assert_eq!(gpg_key_ids, key_ids(&["6C36552B2725F07F"]));
+        for recipient in ["alice@example.com", "6A0951657B04D41B"] {
+            assert_eq!(
+                backend.recipient_key_ids(&recipients(recipient)).unwrap(),
+                gpg_key_ids
+            );
+        }
+    }
+
+    #[test]
+    fn revoked_and_expired_keys_are_not_encrypted_to() {
+        let backend = backend(&[ALICE, EXPIRED_AND_REVOKED]);
+        let entry = temp_path("unusable.gpg");
+        for (recipient, reason) in [
+            ("bob@example.com", "has expired"),
+            ("carol@example.com", "is revoked"),
+        ] {
+            let error = backend
+                .encrypt(b"hunter2", &recipients(recipient), &entry)
+                .err();
+            assert!(
+                matches!(&error, Some(StoreError::RecipientMissing(message)) if message.contains(reason)),
+                "{}: {:?}",
+                recipient,
+                error
+            );
+            assert_eq!(
+                backend.recipient_key_ids(&recipients(recipient)).unwrap(),
+                None
+            );
+        }
+        assert!(!entry.exists());
+    }
+
+    #[test]
+    fn gpg_decrypts_entries_encrypted_by_this_backend() {
+        let home = temp_path("gnupg");
+        fs::create_dir_all(&home).unwrap();
+        let gpg = GpgBackend::with_home(&home);
+        let imported = gpg
+            .command()
+            .args(["--batch", "--import"])
+            .arg(fixture(ALICE))
+            .output()
+            .unwrap();
+        assert!(imported.status.success(), "{:?}", imported);
+
+        let entry = temp_path("for-gpg.gpg");
+        backend(&[ALICE])
+            .encrypt(b"hunter2", &recipients("alice@example.com"), &entry)
+            .unwrap();
+        assert_eq!(gpg.decrypt(&entry).unwrap(), b"hunter2");
+
+        let _ = std::process::Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(&home)
+            .args(["--kill", "gpg-agent"])
+            .status();
+        fs::remove_file(&entry).unwrap();
+        fs::remove_dir_all(&home).unwrap();
+    }
+}
@@ -0,0 +1,594 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+
+use crate::error::StoreError;
+
+/// The hidden directory at the store root that holds the journal of a running re-encryption.
+pub const JOURNAL_DIR: &str = ".reencrypt-journal";
+
+/// A record of a store-wide re-encryption, kept so that an interrupted run can be finished
+/// or undone.
+///
+/// The journal lives in `<store>/.reencrypt-journal/`. Its `journal` file names the folder
+/// being re-encrypted and its recipients. Before an entry is replaced, the original file is
+/// hard-linked (or copied) into `backup/`, so every entry can be restored, and a `replace`
+/// line records the SHA-256 digest of the new file. A `done` line follows once the entry has
+/// been replaced. The whole directory is removed once the run completes.
+///
+/// When [`ReencryptJournal::begin`] finds the journal of an interrupted run for the same
+/// folder and recipients, in any order, the run is resumed and finished entries are skipped.
+/// An interrupted run of the same folder for other recipients is rolled back first (see
+/// [`ReencryptJournal::rollback`]), which refuses to touch entries that were changed since.
+/// An interrupted run of another folder is never rolled back on its own: its `.gpg-id` already
+/// names the new recipients, and restoring the old encryption would let removed recipients
+/// read the entries again.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use password_store_rs::journal::{sync_file, temp_path, ReencryptJournal};
+/// # use std::path::{Path, PathBuf};
+///
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// # let store = Path::new("/home/me/.password-store");
+/// # let recipients = vec!["alice@example.com".to_string()];
+/// # let entries: Vec<PathBuf> = Vec::new();
+/// let journal = ReencryptJournal::begin(store, store, &recipients)?;
+/// for entry in entries {
+///     if journal.is_done(&entry) {
+///         continue;
+///     }
+///     let temp = temp_path(&entry);
+///     // Write the re-encrypted entry to `temp` and check that it decrypts.
+///     sync_file(&temp)?;
+///     journal.replace(&entry, &temp)?;
+/// }
+/// journal.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReencryptJournal {
+    /// The root directory of the password store.
+    store: PathBuf,
+    /// The journal directory, `<store>/.reencrypt-journal`.
+    dir: PathBuf,
+    /// The entries finished by this run or the run it resumes, relative to `store`.
+    done: HashSet<PathBuf>,
+    /// The `journal` file, opened for appending.
+    file: Mutex<File>,
+}
+
+impl ReencryptJournal {
+    /// Starts or resumes the journaled re-encryption of `path` for `recipients`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The root directory of the password store.
+    /// * `path` - The folder being re-encrypted: the store root or one of its subfolders.
+    /// * `recipients` - The recipients the entries are re-encrypted for.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReencryptJournal)` ready to record entries. When it resumes an interrupted run,
+    ///   [`ReencryptJournal::is_done`] reports the entries that run finished.
+    /// * `Err(StoreError::Io)` if the journal cannot be read or written, or an interrupted
+    ///   run cannot be rolled back.
+    /// * `Err(StoreError::InvalidInput)` if an interrupted run of another folder has to be
+    ///   finished first, or an interrupted run of `path` must be rolled back, but an entry it
+    ///   replaced was changed since (see [`ReencryptJournal::rollback`]).
+    pub fn begin(
+        store: &Path,
+        path: &Path,
+        recipients: &[String],
+    ) -> Result<ReencryptJournal, StoreError> {
+        let dir = store.join(JOURNAL_DIR);
+        let journal_file = dir.join("journal");
+        let relative = path.strip_prefix(store).unwrap_or(path);
+
+        ReencryptJournal::check_unfinished(store, path)?;
+        let mut done = HashSet::new();
+        let resume = match fs::read_to_string(&journal_file) {
+            Ok(contents) => {
+                let journal = parse_journal(&contents);
+                if journal.recipients == recipients.iter().cloned().collect() {
+                    done = journal.done;
+                    true
+                } else {
+                    false
+                }
+            }
+            Err(_) => false,
+        };
+
+        if !resume {
+            ReencryptJournal::rollback(store)?;
+            fs::create_dir_all(&dir)
+                .map_err(|e| StoreError::io(format!("Error creating {}", dir.display()), e))?;
+            fs::write(&journal_file, journal_header(relative, recipients)).map_err(|e| {
+                StoreError::io(format!("Failed to write {}", journal_file.display()), e)
+            })?;
+            sync_file(&journal_file)?;
+        }
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&journal_file)
+            .map_err(|e| StoreError::io(format!("Failed to open {}", journal_file.display()), e))?;
+
+        Ok(ReencryptJournal {
+            store: store.to_path_buf(),
+            dir,
+            done,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Checks that no interrupted run of a folder other than `path` is waiting to be finished.
+    ///
+    /// [`ReencryptJournal::begin`] makes the same check, but callers that change the recipients
+    /// of `path` first can make it before changing anything.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if there is no journal, or it is for `path`.
+    /// * `Err(StoreError::InvalidInput)` naming the folder and recipients of the unfinished run.
+    pub fn check_unfinished(store: &Path, path: &Path) -> Result<(), StoreError> {
+        let relative = path.strip_prefix(store).unwrap_or(path);
+        let dir = store.join(JOURNAL_DIR);
+        let Ok(contents) = fs::read_to_string(dir.join("journal")) else {
+            return Ok(());
+        };
+        let journal = parse_journal(&contents);
+        match &journal.path {
+            Some(other) if other != relative => Err(unfinished_run(&dir, &journal)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns whether `entry` was already re-encrypted by the run this journal resumes.
+    pub fn is_done(&self, entry: &Path) -> bool {
+        self.done
+            .contains(entry.strip_prefix(&self.store).unwrap_or(entry))
+    }
+
+    /// Backs up `entry`, atomically replaces it with `temp`, and records it as done.
+    ///
+    /// `temp` must be a finished file on the same filesystem, such as one from
+    /// [`temp_path`] that has been synced. Its digest is recorded before the rename, so that
+    /// a rollback can tell whether `entry` still holds it. The rename and the journal lines
+    /// are all synced to disk before this returns.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once `entry` has its new contents and is recorded.
+    /// * `Err(StoreError::Io)` if the backup, the rename or the journal write fails. The
+    ///   original entry is left in place unless the rename succeeded.
+    pub fn replace(&self, entry: &Path, temp: &Path) -> Result<(), StoreError> {
+        let relative = entry.strip_prefix(&self.store).unwrap_or(entry);
+
+        // A backup left by an interrupted attempt at this entry holds the original, so it
+        // must not be replaced by a file that may already be re-encrypted.
+        let backup = self.dir.join("backup").join(relative);
+        if !backup.exists() {
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent).map_err(|e| {
+                    StoreError::io(format!("Error creating {}", parent.display()), e)
+                })?;
+            }
+            if fs::hard_link(entry, &backup).is_err() {
+                fs::copy(entry, &backup).map_err(|e| {
+                    StoreError::io(format!("Failed to back up {}", entry.display()), e)
+                })?;
+            }
+            sync_file(&backup)?;
+        }
+
+        let new_digest = digest(temp)?.unwrap_or_default();
+        self.append(&format!("replace {} {}", new_digest, relative.display()))?;
+        rename_synced(temp, entry)?;
+        self.append(&format!("done {}", relative.display()))
+    }
+
+    /// Completes the run by removing the journal and its backups.
+    pub fn finish(self) -> Result<(), StoreError> {
+        remove_journal(&self.dir)
+    }
+
+    /// Appends `line` to the journal file and syncs it.
+    fn append(&self, line: &str) -> Result<(), StoreError> {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", line)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| StoreError::io(format!("Failed to update {}", self.dir.display()), e))
+    }
+
+    /// Rolls back an interrupted re-encryption of `store`, if there is one.
+    ///
+    /// A backed-up entry is only restored if it still holds exactly the file the interrupted
+    /// run put there, as recorded by its `replace` line. An entry that still holds its
+    /// original needs nothing. If any entry was changed since, e.g. edited or removed,
+    /// restoring its backup would lose that change, so nothing is restored and the journal
+    /// is kept for the user to resolve by hand. Otherwise the backups are restored, leftover
+    /// temporary files from [`temp_path`] are removed, and the journal is deleted.
+    ///
+    /// The `.gpg-id` of the folder is not restored, so the restored entries are encrypted for
+    /// other recipients than it names until the folder is re-encrypted again.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` if an interrupted run was rolled back.
+    /// * `Ok(false)` if there was no journal.
+    /// * `Err(StoreError::InvalidInput)` if an entry was changed since the run was
+    ///   interrupted. Nothing is restored.
+    /// * `Err(StoreError::Io)` if an entry cannot be read or restored. The journal is kept,
+    ///   so the rollback can be tried again.
+    pub fn rollback(store: &Path) -> Result<bool, StoreError> {
+        let dir = store.join(JOURNAL_DIR);
+        if !dir.exists() {
+            return Ok(false);
+        }
+
+        let journal_file = dir.join("journal");
+        let replaced = match fs::read_to_string(&journal_file) {
+            Ok(contents) => parse_journal(&contents).replaced,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                return Err(StoreError::io(
+                    format!("Failed to read {}", journal_file.display()),
+                    e,
+                ))
+            }
+        };
+
+        // Every entry is checked before any is restored, so a refused rollback changes nothing.
+        let backup = dir.join("backup");
+        let mut backups = Vec::new();
+        collect_files(&backup, &mut backups)?;
+        let mut restore = Vec::new();
+        for file in backups {
+            let relative = file.strip_prefix(&backup).unwrap_or(&file).to_path_buf();
+            let original = store.join(&relative);
+            let current = digest(&original)?;
+            if current.is_some() && current == digest(&file)? {
+                continue;
+            }
+            if current.is_none() || current.as_ref() != replaced.get(&relative) {
+                return Err(StoreError::InvalidInput(format!(
+                    "{} was changed after a re-encryption was interrupted, so the \
+                     re-encryption cannot be rolled back. Restore what you need from {} by \
+                     hand, then remove {}.",
+                    original.display(),
+                    backup.display(),
+                    dir.display()
+                )));
+            }
+            restore.push((file, original));
+        }
+        for (file, original) in restore {
+            rename_synced(&file, &original)?;
+        }
+
+        remove_temp_files(store)?;
+        remove_journal(&dir)?;
+        Ok(true)
+    }
+}
+
+/// Returns the hidden temporary file next to `entry` that its new contents are written to.
+///
+/// For `/store/web/example.com.gpg` this is `/store/web/.example.com.gpg.reencrypt-tmp`, so
+/// it is skipped when the store is listed and is on the same filesystem as `entry`.
+pub fn temp_path(entry: &Path) -> PathBuf {
+    let name = entry
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    entry.with_file_name(format!(".{}.reencrypt-tmp", name))
+}
+
+/// Flushes the contents of the file at `path` to disk.
+pub fn sync_file(path: &Path) -> Result<(), StoreError> {
+    File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(|e| StoreError::io(format!("Failed to sync {}", path.display()), e))
+}
+
+/// Renames `from` to `to`, replacing `to` atomically, and syncs the directory of `to` so the
+/// rename survives a crash.
+pub fn rename_synced(from: &Path, to: &Path) -> Result<(), StoreError> {
+    fs::rename(from, to).map_err(|e| {
+        StoreError::io(
+            format!("Failed to rename {} to {}", from.display(), to.display()),
+            e,
+        )
+    })?;
+    match to.parent() {
+        Some(parent) => sync_file(parent),
+        None => Ok(()),
+    }
+}
+
+/// Returns the first lines of a journal: the folder and the recipients.
+fn journal_header(relative: &Path, recipients: &[String]) -> String {
+    let mut header = format!("path {}\n", relative.display());
+    for recipient in recipients {
+        header.push_str(&format!("recipient {}\n", recipient));
+    }
+    header
+}
+
+/// The contents of a journal file.
+#[derive(Debug, Default)]
+struct JournalContents {
+    /// The folder being re-encrypted, relative to the store, from the `path` line.
+    path: Option<PathBuf>,
+    /// The recipients of the `recipient` lines. Their order does not matter.
+    recipients: BTreeSet<String>,
+    /// The entries of the `done` lines.
+    done: HashSet<PathBuf>,
+    /// The digests of the new files of the `replace` lines, by entry.
+    replaced: HashMap<PathBuf, String>,
+}
+
+/// Splits a journal into its folder, its recipients, the finished entries and the digests of
+/// the new files.
+fn parse_journal(contents: &str) -> JournalContents {
+    let mut journal = JournalContents::default();
+    for line in contents.lines() {
+        if let Some(path) = line.strip_prefix("path ") {
+            journal.path = Some(PathBuf::from(path));
+        } else if let Some(recipient) = line.strip_prefix("recipient ") {
+            journal.recipients.insert(recipient.to_string());
+        } else if let Some(entry) = line.strip_prefix("done ") {
+            journal.done.insert(PathBuf::from(entry));
+        } else if let Some((digest, entry)) = line
+            .strip_prefix("replace ")
+            .and_then(|rest| rest.split_once(' '))
+        {
+            journal
+                .replaced
+                .insert(PathBuf::from(entry), digest.to_string());
+        }
+    }
+    journal
+}
+
+/// Returns the error for an interrupted run of another folder, which has to be finished
+/// before anything else is re-encrypted.
+fn unfinished_run(dir: &Path, journal: &JournalContents) -> StoreError {
+    let folder = match &journal.path {
+        Some(path) if !path.as_os_str().is_empty() => path.display().to_string(),
+        _ => "the store root".to_string(),
+    };
+    let recipients: Vec<&str> = journal.recipients.iter().map(String::as_str).collect();
+    StoreError::InvalidInput(format!(
+        "A re-encryption of {} for {} did not finish. Re-encrypt {} for the same recipients \
+         again (e.g. run the same init again) to finish it, or remove {} to keep its entries as \
+         they are now.",
+        folder,
+        recipients.join(", "),
+        folder,
+        dir.display()
+    ))
+}
+
+/// Returns the SHA-256 digest of the file at `path` in hex, or `None` if it does not exist.
+fn digest(path: &Path) -> Result<Option<String>, StoreError> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(HEXLOWER.encode(&Sha256::digest(contents)))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(StoreError::io(
+            format!("Failed to read {}", path.display()),
+            e,
+        )),
+    }
+}
+
+/// Recursively collects the files under `dir`. A missing `dir` has no files.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), StoreError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let entries = fs::read_dir(dir)
+        .map_err(|e| StoreError::io(format!("Failed to read directory {}", dir.display()), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Removes every leftover temporary file (see [`temp_path`]) under `dir`, skipping `.git`.
+fn remove_temp_files(dir: &Path) -> Result<(), StoreError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| StoreError::io(format!("Failed to read directory {}", dir.display()), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            if name != ".git" && name != JOURNAL_DIR {
+                remove_temp_files(&path)?;
+            }
+        } else if name.starts_with('.') && name.ends_with(".reencrypt-tmp") {
+            fs::remove_file(&path)
+                .map_err(|e| StoreError::io(format!("Error removing {}", path.display()), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the journal directory `dir` and everything in it.
+fn remove_journal(dir: &Path) -> Result<(), StoreError> {
+    fs::remove_dir_all(dir)
+        .map_err(|e| StoreError::io(format!("Error removing {}", dir.display()), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty store directory for the test `name`, with the entries `a.gpg` and
+    /// `web/b.gpg`.
+    fn store(name: &str) -> PathBuf {
+        let store = std::env::temp_dir().join(format!(
+            "password-store-rs-journal-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&store);
+        fs::create_dir_all(store.join("web")).unwrap();
+        fs::write(store.join("a.gpg"), "a for alice").unwrap();
+        fs::write(store.join("web/b.gpg"), "b for alice").unwrap();
+        store
+    }
+
+    /// Re-encrypts `entry` to `contents` through `journal`, like `reencrypt_path` does.
+    fn replace(journal: &ReencryptJournal, entry: &Path, contents: &str) {
+        let temp = temp_path(entry);
+        fs::write(&temp, contents).unwrap();
+        journal.replace(entry, &temp).unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn recipients(recipient: &str) -> Vec<String> {
+        vec![recipient.to_string()]
+    }
+
+    #[test]
+    fn an_interrupted_run_is_resumed_for_the_same_recipients() {
+        let store = store("resume");
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        replace(&journal, &store.join("a.gpg"), "a for bob");
+        drop(journal);
+
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        assert!(journal.is_done(&store.join("a.gpg")));
+        assert!(!journal.is_done(&store.join("web/b.gpg")));
+        replace(&journal, &store.join("web/b.gpg"), "b for bob");
+        journal.finish().unwrap();
+
+        assert_eq!(read(&store.join("a.gpg")), "a for bob");
+        assert_eq!(read(&store.join("web/b.gpg")), "b for bob");
+        assert!(!store.join(JOURNAL_DIR).exists());
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn the_order_of_the_recipients_does_not_matter() {
+        let store = store("order");
+        let both = vec!["bob".to_string(), "carol".to_string()];
+        let journal = ReencryptJournal::begin(&store, &store, &both).unwrap();
+        replace(&journal, &store.join("a.gpg"), "a for bob and carol");
+        drop(journal);
+
+        let reversed = vec!["carol".to_string(), "bob".to_string()];
+        let journal = ReencryptJournal::begin(&store, &store, &reversed).unwrap();
+        assert!(journal.is_done(&store.join("a.gpg")));
+        assert_eq!(read(&store.join("a.gpg")), "a for bob and carol");
+        journal.finish().unwrap();
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn a_partial_failure_is_not_rolled_back_by_another_folder() {
+        let store = store("other-folder");
+        fs::create_dir_all(store.join("work")).unwrap();
+        // The store root was re-encrypted for bob, and web/b.gpg failed.
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        replace(&journal, &store.join("a.gpg"), "a for bob");
+        drop(journal);
+
+        let error =
+            ReencryptJournal::begin(&store, &store.join("work"), &recipients("carol")).err();
+        assert!(
+            matches!(&error, Some(StoreError::InvalidInput(message)) if message.contains("bob")),
+            "{:?}",
+            error
+        );
+        // alice must not get a.gpg back, and the run can still be finished.
+        assert_eq!(read(&store.join("a.gpg")), "a for bob");
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        assert!(journal.is_done(&store.join("a.gpg")));
+        replace(&journal, &store.join("web/b.gpg"), "b for bob");
+        journal.finish().unwrap();
+
+        let journal = ReencryptJournal::begin(&store, &store.join("work"), &recipients("carol"));
+        journal.unwrap().finish().unwrap();
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn an_interrupted_run_is_rolled_back_for_other_recipients() {
+        let store = store("rollback");
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        replace(&journal, &store.join("a.gpg"), "a for bob");
+        fs::write(temp_path(&store.join("web/b.gpg")), "half of b").unwrap();
+        drop(journal);
+
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("carol")).unwrap();
+        assert!(!journal.is_done(&store.join("a.gpg")));
+        assert_eq!(read(&store.join("a.gpg")), "a for alice");
+        assert_eq!(read(&store.join("web/b.gpg")), "b for alice");
+        assert!(!temp_path(&store.join("web/b.gpg")).exists());
+        journal.finish().unwrap();
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn an_entry_changed_after_the_interruption_is_not_rolled_back() {
+        let store = store("changed");
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        replace(&journal, &store.join("a.gpg"), "a for bob");
+        replace(&journal, &store.join("web/b.gpg"), "b for bob");
+        drop(journal);
+        fs::write(store.join("web/b.gpg"), "b edited for bob").unwrap();
+
+        let error = ReencryptJournal::begin(&store, &store, &recipients("carol")).err();
+        assert!(
+            matches!(&error, Some(StoreError::InvalidInput(message)) if message.contains("b.gpg")),
+            "{:?}",
+            error
+        );
+        // Nothing was restored, and the journal is kept to resolve by hand.
+        assert_eq!(read(&store.join("a.gpg")), "a for bob");
+        assert_eq!(read(&store.join("web/b.gpg")), "b edited for bob");
+        assert_eq!(
+            read(&store.join(JOURNAL_DIR).join("backup/web/b.gpg")),
+            "b for alice"
+        );
+
+        fs::remove_file(store.join("web/b.gpg")).unwrap();
+        assert!(ReencryptJournal::rollback(&store).is_err());
+        fs::remove_dir_all(&store).unwrap();
+    }
+
+    #[test]
+    fn an_entry_that_was_not_replaced_needs_no_restore() {
+        let store = store("unreplaced");
+        let journal = ReencryptJournal::begin(&store, &store, &recipients("bob")).unwrap();
+        // A backup taken just before the run stopped, with the entry still in place.
+        let backup = store.join(JOURNAL_DIR).join("backup/a.gpg");
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        fs::copy(store.join("a.gpg"), &backup).unwrap();
+        drop(journal);
+
+        assert!(ReencryptJournal::rollback(&store).unwrap());
+        assert_eq!(read(&store.join("a.gpg")), "a for alice");
+        assert!(!store.join(JOURNAL_DIR).exists());
+        assert!(!ReencryptJournal::rollback(&store).unwrap());
+        fs::remove_dir_all(&store).unwrap();
+    }
+}
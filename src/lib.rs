@@ -0,0 +1,37 @@
+//! A Rust implementation of the standard Unix password manager, `pass`.
+//!
+//! The library exposes [`PasswordStore`], which operates on a store directory of GPG
+//! encrypted entries and reports failures as a [`StoreError`] instead of printing or exiting, so that other
+//! tools can embed the store. The `password-store-rs` binary is a thin command-line
+//! interface on top of it.
+//!
+//! Cryptography is pluggable through the [`CryptoBackend`] trait. By default the `gpg`
+//! executable does the work (see [`integrations::gpg::GpgBackend`]).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use password_store_rs::utils::store_dir;
+//! use password_store_rs::PasswordStore;
+//!
+//! # fn main() -> Result<(), password_store_rs::StoreError> {
+//! let store = PasswordStore::open(store_dir()?)?;
+//! let secret = store.get("web/example.com")?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod crypto;
+pub mod entry;
+pub mod error;
+pub mod integrations;
+pub mod journal;
+pub mod otp;
+pub mod store;
+pub mod tree;
+pub mod utils;
+
+pub use crypto::CryptoBackend;
+pub use entry::PasswordEntry;
+pub use error::StoreError;
+pub use store::PasswordStore;
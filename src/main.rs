@@ -1,27 +1,58 @@
 mod commands;
-mod integrations;
-mod utils;
 
+use clap::builder::RangedU64ValueParser;
 use clap::{arg, Arg, ArgAction, Command as ClapCommand};
+use commands::show::Selection;
+use commands::{OutputFormat, CLIP_RESTORE_SUBCOMMAND};
+use password_store_rs::integrations::clipboard::restore_after_timeout;
 
 /// Constructs the command-line interface (CLI) for the password store application.
 ///
 /// This function builds and returns a [`clap::Command`] that defines the structure
 /// of the CLI for the password manager. The CLI includes the following subcommands:
 ///
-/// - **init**: Initializes a new password store by specifying a GPG key identifier and an optional subfolder.
+/// - **init**: Initializes a new password store by specifying one or more GPG key identifiers (or age
+///   recipients with `--age`) and an optional subfolder. `--dry-run` (`-n`) lists the entries that
+///   would be re-encrypted without changing anything.
 /// - **add**: Adds a new password entry to the store. The password can be provided directly as an argument,
 ///   or, if omitted, the user will be prompted to enter it interactively. In addition, the "add" subcommand
 ///   supports extra options:
 ///     - `--multiline` (`-m`): Read the password input in multiline mode (until EOF).
 ///     - `--echo` (`-e`): Read the password with echo enabled (i.e. visible input).
 ///     - `--force` (`-f`): Force overwrite an existing entry without prompting for confirmation.
-/// - **show**: Displays an existing password entry (and can optionally place it on the clipboard).
+///     - `--field key=value`: Store a `key: value` field after the password. May be repeated.
+/// - **edit**: Opens a password entry in `$EDITOR` using a private temporary file and saves it if changed.
+/// - **generate**: Generates a random password for a new or existing entry. Supports `--no-symbols`
+///   (`-n`), `--in-place` (`-i`, replace only the first line), `--force` (`-f`) and `--clip` (`-c`).
+/// - **mv** / **cp**: Renames or copies a password entry or directory, re-encrypting it when the
+///   destination uses different recipients. `--force` (`-f`) replaces an existing destination.
+/// - **rm**: Removes a password entry, or a directory with `--recursive` (`-r`), after confirmation
+///   unless `--force` (`-f`) is given. The removal is committed to Git.
+/// - **show**: Displays an existing password entry. With `--clip[=line]` the given line (the first by
+///   default) is placed on the clipboard instead and cleared after `PASSWORD_STORE_CLIP_TIME` seconds.
+///   `--field key` shows only the value of a `key: value` field, and `--line N` only line N.
+///   For a folder (or the whole store), it lists the entries as a tree, limited to `--depth` levels.
+///   Also available as **ls**.
 /// - **find**: Searches for passwords matching a specified query (pass-name).
+/// - **git**: Runs a Git command inside the password store. `git init` also commits the existing
+///   entries and configures a `gpg` diff driver so that history shows decrypted changes.
+/// - **grep**: Searches the decrypted contents of every entry for a regular expression. Supports
+///   `--ignore-case` (`-i`), `--fixed-strings` (`-F`) and `--word-regexp` (`-w`).
+/// - **otp**: Works with the `otpauth://` URI stored in an entry:
+///     - `otp [show] pass-name`: Prints the current TOTP or HOTP code, or copies it with `--clip` (`-c`).
+///       HOTP entries get their counter incremented and are re-encrypted.
+///     - `otp insert pass-name [uri]`: Adds a URI to an entry, or creates one. Supports `--echo` (`-e`)
+///       and `--force` (`-f`).
+///     - `otp uri pass-name`: Prints the URI, or copies it with `--clip` (`-c`).
+///     - `otp validate pass-name`: Checks the URI and describes it.
+///
+/// The global `--format` option (`text`, `json` or `yaml`) makes **show** and **find** print
+/// entries and lists of entry paths for scripts, and makes every command report errors as an
+/// object with `error` and `message` on standard error.
 ///
 /// # Examples
 ///
-/// ```rust
+/// ```rust,ignore
 /// let app = cli();
 /// let matches = app.get_matches();
 /// // Dispatch to appropriate command based on the matches...
@@ -35,13 +66,35 @@ fn cli() -> ClapCommand {
         .version("1.0")
         .author("Ricky Nelson <rickyn@socketwiz.com>")
         .about("A GPG-based password manager inspired by `pass`, built in Rust")
+        .arg(
+            arg!(--format <format> "Print entries, lists and errors as text, JSON or YAML")
+                .value_parser(["text", "json", "yaml"])
+                .default_value("text")
+                .global(true),
+        )
         .subcommand(
             ClapCommand::new("init")
                 .about("Initialize new password storage and use gpg-id for encryption")
-                .arg(arg!([GPGID] "Specifies a GPG key identifier").value_name("gpg-id"))
+                .arg(
+                    arg!([GPGID] "Specifies one or more GPG key identifiers")
+                        .value_name("gpg-id")
+                        .num_args(1..),
+                )
                 .arg(arg!(-p --path [subfolder] "Specifies an optional subfolder").id("subfolder"))
                 // Add a flag to force auto-generation even if a key was provided.
-                .arg(arg!(-a --auto "Automatically generate a new GPG key").action(ArgAction::SetTrue)),
+                .arg(arg!(-a --auto "Automatically generate a new GPG key").action(ArgAction::SetTrue))
+                .arg(
+                    arg!(--age "Encrypt with age; the identifiers are age recipients (age1...)")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("auto"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .short('n')
+                        .long("dry-run")
+                        .help("Show which entries would be re-encrypted without changing anything")
+                        .action(ArgAction::SetTrue),
+                ),
         )
         .subcommand(
             ClapCommand::new("add")
@@ -68,18 +121,242 @@ fn cli() -> ClapCommand {
                         .long("force")
                         .help("Force overwrite an existing entry")
                         .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("field")
+                        .long("field")
+                        .value_name("key=value")
+                        .help("Store a `key: value` field after the password (repeatable)")
+                        .action(ArgAction::Append)
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("edit")
+                .about("Insert a new password or edit an existing password using an editor")
+                .arg(arg!(<PASS_NAME> "The name of the password entry").value_name("pass-name")),
+        )
+        .subcommand(
+            ClapCommand::new("generate")
+                .about("Generate a new password")
+                .arg(arg!(<PASS_NAME> "The name of the password entry").value_name("pass-name"))
+                .arg(
+                    arg!([LENGTH] "The length of the password (defaults to $PASSWORD_STORE_GENERATED_LENGTH or 25)")
+                        .value_name("pass-length")
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+                )
+                .arg(
+                    Arg::new("no-symbols")
+                        .short('n')
+                        .long("no-symbols")
+                        .help("Use only alphanumeric characters")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("in-place")
+                        .short('i')
+                        .long("in-place")
+                        .help("Replace only the first line of an existing entry")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("force")
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Force overwrite an existing entry")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("clip")
+                        .short('c')
+                        .long("clip")
+                        .help("Put the password on the clipboard (clears in $CLIP_TIME seconds)")
+                        .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("mv")
+                .about("Rename or move a password or directory, reencrypting as necessary")
+                .arg(arg!(<OLD_PATH> "The entry or directory to move").value_name("old-path"))
+                .arg(arg!(<NEW_PATH> "The new name").value_name("new-path"))
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Overwrite an existing destination without prompting")
+                        .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("cp")
+                .about("Copy a password or directory, reencrypting as necessary")
+                .arg(arg!(<OLD_PATH> "The entry or directory to copy").value_name("old-path"))
+                .arg(arg!(<NEW_PATH> "The name of the copy").value_name("new-path"))
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Overwrite an existing destination without prompting")
+                        .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("rm")
+                .about("Remove an existing password or directory")
+                .arg(arg!(<PASS_NAME> "The name of the password entry").value_name("pass-name"))
+                .arg(
+                    Arg::new("recursive")
+                        .short('r')
+                        .long("recursive")
+                        .help("Remove a directory and its contents")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Remove without prompting")
+                        .action(ArgAction::SetTrue)
                 ),
         )
         .subcommand(
             ClapCommand::new("show")
                 .about("Show an existing password")
+                .visible_alias("ls")
                 .arg(arg!(<PASS_NAME> "Specifies a pass-name").value_name("pass-name").required(false))
-                .arg(arg!(-c --clip "Put the password on the clipboard (clears in $CLIP_TIME seconds)"))
+                .arg(
+                    arg!(-c --clip [line] "Put the password on the clipboard (clears in $CLIP_TIME seconds)")
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+                        .require_equals(true)
+                        .default_missing_value("1"),
+                )
+                .arg(
+                    arg!(--field <key> "Show only the value of the `key: value` field")
+                        .conflicts_with("line"),
+                )
+                .arg(
+                    arg!(--line <N> "Show only line N of the entry (1 is the password)")
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+                        .conflicts_with("clip"),
+                )
+                .arg(
+                    arg!(--depth <levels> "Only list this many levels of folders")
+                        .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+                )
         )
         .subcommand(
             ClapCommand::new("find")
                 .about("List passwords that match a pass-name.")
-                .arg(arg!(<PASS_NAMES> "Specifies a pass-name").value_name("pass-names"))
+                .arg(arg!(<PASS_NAMES> "Specifies a pass-name").value_name("pass-names").num_args(1..))
+        )
+        .subcommand(
+            ClapCommand::new("git")
+                .about("Run a git command inside the password store")
+                .disable_help_flag(true)
+                .arg(
+                    Arg::new("ARGS")
+                        .value_name("git-command-args")
+                        .help("Arguments passed to git, e.g. `init` or `push`")
+                        .required(true)
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("grep")
+                .about("Search for password files containing a pattern when decrypted")
+                .arg(arg!(<PATTERN> "The regular expression to search for").value_name("search-string"))
+                .arg(
+                    Arg::new("ignore-case")
+                        .short('i')
+                        .long("ignore-case")
+                        .help("Ignore case distinctions")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("fixed-strings")
+                        .short('F')
+                        .long("fixed-strings")
+                        .help("Interpret the pattern as a literal string")
+                        .action(ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("word-regexp")
+                        .short('w')
+                        .long("word-regexp")
+                        .help("Match only whole words")
+                        .action(ArgAction::SetTrue)
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("otp")
+                .about("Generate one-time passwords from otpauth:// URIs stored in entries")
+                .args_conflicts_with_subcommands(true)
+                .arg_required_else_help(true)
+                .arg(arg!([PASS_NAME] "The entry that holds the otpauth:// URI").value_name("pass-name"))
+                .arg(
+                    Arg::new("clip")
+                        .short('c')
+                        .long("clip")
+                        .help("Put the code on the clipboard (clears in $CLIP_TIME seconds)")
+                        .action(ArgAction::SetTrue)
+                )
+                .subcommand(
+                    ClapCommand::new("show")
+                        .about("Show the current code, incrementing the counter of HOTP entries")
+                        .arg(arg!(<PASS_NAME> "The entry that holds the otpauth:// URI").value_name("pass-name"))
+                        .arg(
+                            Arg::new("clip")
+                                .short('c')
+                                .long("clip")
+                                .help("Put the code on the clipboard (clears in $CLIP_TIME seconds)")
+                                .action(ArgAction::SetTrue)
+                        ),
+                )
+                .subcommand(
+                    ClapCommand::new("insert")
+                        .about("Add an otpauth:// URI to an entry, or create an entry for it")
+                        .arg(arg!(<PASS_NAME> "The entry to add the URI to").value_name("pass-name"))
+                        .arg(arg!([URI] "The otpauth:// URI (if not provided, it is read from stdin or prompted for)").value_name("uri"))
+                        .arg(
+                            Arg::new("echo")
+                                .short('e')
+                                .long("echo")
+                                .help("Read the URI with echo enabled")
+                                .action(ArgAction::SetTrue)
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .short('f')
+                                .long("force")
+                                .help("Replace an existing URI without prompting")
+                                .action(ArgAction::SetTrue)
+                        ),
+                )
+                .subcommand(
+                    ClapCommand::new("uri")
+                        .about("Show the otpauth:// URI of an entry")
+                        .arg(arg!(<PASS_NAME> "The entry that holds the otpauth:// URI").value_name("pass-name"))
+                        .arg(
+                            Arg::new("clip")
+                                .short('c')
+                                .long("clip")
+                                .help("Put the URI on the clipboard (clears in $CLIP_TIME seconds)")
+                                .action(ArgAction::SetTrue)
+                        ),
+                )
+                .subcommand(
+                    ClapCommand::new("validate")
+                        .about("Check the otpauth:// URI of an entry")
+                        .arg(arg!(<PASS_NAME> "The entry that holds the otpauth:// URI").value_name("pass-name")),
+                ),
+        )
+        .subcommand(
+            // Internal: the detached process that clears the clipboard after `show --clip`.
+            ClapCommand::new(CLIP_RESTORE_SUBCOMMAND)
+                .hide(true)
+                .arg(arg!(<SECONDS>).value_parser(clap::value_parser!(u64))),
         )
 }
 
@@ -91,9 +368,19 @@ fn cli() -> ClapCommand {
 /// 3. Dispatches to the appropriate command handler based on the provided subcommand:
 ///    - **init**: Initializes a new password store.
 ///    - **add**: Adds a new password entry to the store.
+///    - **edit**: Edits a password entry with the user's editor.
+///    - **generate**: Generates a random password and stores it.
+///    - **mv** / **cp**: Renames or copies a password entry or directory.
+///    - **rm**: Removes a password entry or directory from the store.
 ///    - **show**: Displays an existing password entry (and optionally places it on the clipboard).
 ///    - **find**: Searches for password entries matching a query.
+///    - **git**: Runs Git inside the password store.
+///    - **grep**: Searches the decrypted contents of all entries.
+///    - **otp**: Shows, adds, prints or checks the one-time password URI of an entry.
 /// 4. If no valid subcommand is provided, it calls `cmd_show` to display the entire password store.
+/// 5. If the command fails, prints the [`password_store_rs::StoreError`] in the chosen `--format`
+///    (see [`OutputFormat::print_error`]) and exits with its exit code (see
+///    [`password_store_rs::StoreError::exit_code`]).
 ///
 /// # Example
 ///
@@ -107,17 +394,26 @@ fn cli() -> ClapCommand {
 fn main() {
     let app = cli();
     let matches = app.clone().get_matches();
+    let format = matches
+        .get_one::<String>("format")
+        .and_then(|name| OutputFormat::from_name(name))
+        .unwrap_or(OutputFormat::Text);
 
-    match matches.subcommand() {
+    let result = match matches.subcommand() {
         Some(("init", sub_matches)) => {
-            let gpg_id_opt = sub_matches.get_one::<String>("GPGID").map(String::as_str);
+            let gpg_ids: Vec<&str> = sub_matches
+                .get_many::<String>("GPGID")
+                .map(|ids| ids.map(String::as_str).collect())
+                .unwrap_or_default();
             let subfolder = sub_matches
                 .get_one::<String>("subfolder")
                 .map(String::as_str)
                 .unwrap_or("");
             let auto = sub_matches.get_flag("auto");
+            let age = sub_matches.get_flag("age");
+            let dry_run = sub_matches.get_flag("dry-run");
 
-            commands::init::cmd_init(gpg_id_opt, subfolder, auto);
+            commands::init::cmd_init(&gpg_ids, subfolder, auto, age, dry_run)
         }
         Some(("add", sub_matches)) => {
             let pass_name = sub_matches
@@ -129,27 +425,154 @@ fn main() {
             let multiline = sub_matches.get_flag("multiline");
             let echo = sub_matches.get_flag("echo");
             let force = sub_matches.get_flag("force");
+            let fields: Vec<&str> = sub_matches
+                .get_many::<String>("field")
+                .unwrap_or_default()
+                .map(String::as_str)
+                .collect();
+
+            commands::add::cmd_add(pass_name, maybe_password, multiline, echo, force, &fields)
+        }
+        Some(("edit", sub_matches)) => {
+            let pass_name = sub_matches
+                .get_one::<String>("PASS_NAME")
+                .expect("PASS_NAME is required");
+
+            commands::edit::cmd_edit(pass_name)
+        }
+        Some(("generate", sub_matches)) => {
+            let pass_name = sub_matches
+                .get_one::<String>("PASS_NAME")
+                .expect("PASS_NAME is required");
+            let length = sub_matches.get_one::<usize>("LENGTH").copied();
+            let no_symbols = sub_matches.get_flag("no-symbols");
+            let in_place = sub_matches.get_flag("in-place");
+            let force = sub_matches.get_flag("force");
+            let clip = sub_matches.get_flag("clip");
+
+            commands::generate::cmd_generate(pass_name, length, no_symbols, in_place, force, clip)
+        }
+        Some((name @ ("mv" | "cp"), sub_matches)) => {
+            let old_path = sub_matches
+                .get_one::<String>("OLD_PATH")
+                .expect("OLD_PATH is required");
+            let new_path = sub_matches
+                .get_one::<String>("NEW_PATH")
+                .expect("NEW_PATH is required");
+            let force = sub_matches.get_flag("force");
+
+            commands::copy_move::cmd_copy_move(old_path, new_path, force, name == "mv")
+        }
+        Some(("rm", sub_matches)) => {
+            let pass_name = sub_matches
+                .get_one::<String>("PASS_NAME")
+                .expect("PASS_NAME is required");
+            let recursive = sub_matches.get_flag("recursive");
+            let force = sub_matches.get_flag("force");
 
-            commands::add::cmd_add(pass_name, maybe_password, multiline, echo, force);
+            commands::rm::cmd_rm(pass_name, recursive, force)
         }
         Some(("show", sub_matches)) => {
             let pass_name = sub_matches
                 .get_one::<String>("PASS_NAME")
                 .map(String::as_str)
                 .unwrap_or("");
+            let clip = sub_matches.get_one::<usize>("clip").copied();
+            let depth = sub_matches.get_one::<usize>("depth").copied();
+            let selection = match (
+                sub_matches.get_one::<String>("field"),
+                sub_matches.get_one::<usize>("line"),
+            ) {
+                (Some(key), _) => Selection::Field(key),
+                (None, Some(line)) => Selection::Line(*line),
+                (None, None) => clip.map_or(Selection::All, Selection::Line),
+            };
 
-            commands::show::cmd_show(pass_name);
+            commands::show::cmd_show(pass_name, selection, clip.is_some(), depth, format)
         }
         Some(("find", sub_matches)) => {
-            let pass_names = sub_matches
-                .get_one::<String>("PASS_NAMES")
-                .expect("PASS_NAMES is required");
+            let pass_names: Vec<&str> = sub_matches
+                .get_many::<String>("PASS_NAMES")
+                .expect("PASS_NAMES is required")
+                .map(String::as_str)
+                .collect();
+
+            commands::find::cmd_find(&pass_names, format)
+        }
+        Some(("git", sub_matches)) => {
+            let args: Vec<String> = sub_matches
+                .get_many::<String>("ARGS")
+                .expect("ARGS is required")
+                .cloned()
+                .collect();
+
+            commands::git::cmd_git(&args)
+        }
+        Some(("grep", sub_matches)) => {
+            let pattern = sub_matches
+                .get_one::<String>("PATTERN")
+                .expect("PATTERN is required");
+            let ignore_case = sub_matches.get_flag("ignore-case");
+            let fixed_strings = sub_matches.get_flag("fixed-strings");
+            let word_regexp = sub_matches.get_flag("word-regexp");
+
+            commands::grep::cmd_grep(pattern, ignore_case, fixed_strings, word_regexp)
+        }
+        Some(("otp", sub_matches)) => match sub_matches.subcommand() {
+            Some(("insert", otp_matches)) => {
+                let pass_name = otp_matches
+                    .get_one::<String>("PASS_NAME")
+                    .expect("PASS_NAME is required");
+                let maybe_uri = otp_matches.get_one::<String>("URI").map(|s| s.as_str());
+                let echo = otp_matches.get_flag("echo");
+                let force = otp_matches.get_flag("force");
+
+                commands::otp::cmd_otp_insert(pass_name, maybe_uri, echo, force)
+            }
+            Some(("uri", otp_matches)) => {
+                let pass_name = otp_matches
+                    .get_one::<String>("PASS_NAME")
+                    .expect("PASS_NAME is required");
+                let clip = otp_matches.get_flag("clip");
 
-            commands::find::cmd_find(pass_names);
+                commands::otp::cmd_otp_uri(pass_name, clip)
+            }
+            Some(("validate", otp_matches)) => {
+                let pass_name = otp_matches
+                    .get_one::<String>("PASS_NAME")
+                    .expect("PASS_NAME is required");
+
+                commands::otp::cmd_otp_validate(pass_name)
+            }
+            // `otp show pass-name`, or just `otp pass-name`.
+            show => {
+                let otp_matches = match show {
+                    Some((_, otp_matches)) => otp_matches,
+                    None => sub_matches,
+                };
+                let pass_name = otp_matches
+                    .get_one::<String>("PASS_NAME")
+                    .expect("PASS_NAME is required");
+                let clip = otp_matches.get_flag("clip");
+
+                commands::otp::cmd_otp_show(pass_name, clip)
+            }
+        },
+        Some((CLIP_RESTORE_SUBCOMMAND, sub_matches)) => {
+            let seconds = *sub_matches
+                .get_one::<u64>("SECONDS")
+                .expect("SECONDS is required");
+
+            restore_after_timeout(seconds)
         }
         _ => {
             // When no subcommand is provided, display the password store.
-            commands::show::cmd_show("");
+            commands::show::cmd_show("", Selection::All, false, None, format)
         }
+    };
+
+    if let Err(e) = result {
+        format.print_error(&e);
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,517 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32_NOPAD;
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::{Digest, FixedOutputReset};
+use hmac::{Mac, SimpleHmac};
+use percent_encoding::percent_decode_str;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::entry::PasswordEntry;
+use crate::error::StoreError;
+
+/// The scheme that starts every one-time password URI.
+pub const OTPAUTH_SCHEME: &str = "otpauth://";
+
+/// The hash function a one-time password is computed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    /// HMAC-SHA-1, the default.
+    Sha1,
+    /// HMAC-SHA-256.
+    Sha256,
+    /// HMAC-SHA-512.
+    Sha512,
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpAlgorithm::Sha1 => write!(f, "SHA1"),
+            OtpAlgorithm::Sha256 => write!(f, "SHA256"),
+            OtpAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+/// Whether a one-time password changes with time or with every use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    /// A time-based password (RFC 6238) that changes every `period` seconds.
+    Totp { period: u64 },
+    /// A counter-based password (RFC 4226). The counter must be incremented before each code.
+    Hotp { counter: u64 },
+}
+
+/// A one-time password secret, parsed from an `otpauth://` URI in the
+/// [Key URI Format](https://github.com/google/google-authenticator/wiki/Key-Uri-Format):
+///
+/// ```text
+/// otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example
+/// otpauth://hotp/Example:alice?secret=JBSWY3DPEHPK3PXP&counter=42&algorithm=SHA256&digits=8
+/// ```
+///
+/// `secret` is required and base32-encoded. `algorithm` (`SHA1`, `SHA256` or `SHA512`),
+/// `digits` (6 to 10) and, for TOTP, `period` default to `SHA1`, 6 and 30 seconds. HOTP
+/// requires a `counter`.
+///
+/// The URI is kept as it was written, so its [`fmt::Display`] form gives it back unchanged,
+/// apart from the counter after [`OtpUri::increment_counter`].
+///
+/// # Examples
+///
+/// ```rust
+/// use password_store_rs::otp::{unix_time, OtpUri};
+///
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let uri = OtpUri::parse("otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP")?;
+/// let now = unix_time()?;
+/// println!("{} ({}s left)", uri.code(now), uri.seconds_left(now).unwrap());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtpUri {
+    /// Time- or counter-based, with the period or counter.
+    kind: OtpKind,
+    /// The decoded label, usually `issuer:account`.
+    label: String,
+    /// The `issuer` parameter, or the part of the label before `:`.
+    issuer: Option<String>,
+    /// The decoded shared secret.
+    secret: Vec<u8>,
+    /// The hash function.
+    algorithm: OtpAlgorithm,
+    /// How many digits a code has.
+    digits: u32,
+    /// The URI as written.
+    uri: String,
+}
+
+impl OtpUri {
+    /// Parses an `otpauth://totp/...` or `otpauth://hotp/...` URI.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(OtpUri)` with the secret and parameters of `uri`.
+    /// * `Err(StoreError::InvalidInput)` if `uri` is not an `otpauth://` URI, has an unknown
+    ///   type or algorithm, lacks a valid base32 `secret` (or `counter` for HOTP), or has a
+    ///   `digits` or `period` out of range.
+    pub fn parse(uri: &str) -> Result<OtpUri, StoreError> {
+        let uri = uri.trim();
+        let invalid = |reason: &str| {
+            StoreError::InvalidInput(format!("'{}' is not a valid OTP URI: {}.", uri, reason))
+        };
+
+        let rest = uri
+            .get(..OTPAUTH_SCHEME.len())
+            .filter(|scheme| scheme.eq_ignore_ascii_case(OTPAUTH_SCHEME))
+            .map(|_| &uri[OTPAUTH_SCHEME.len()..])
+            .ok_or_else(|| invalid("it must start with otpauth://"))?;
+        let (otp_type, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = decode(label);
+
+        let mut secret = None;
+        let mut issuer = None;
+        let mut algorithm = OtpAlgorithm::Sha1;
+        let mut digits = 6;
+        let mut period = 30;
+        let mut counter = None;
+        for param in query.split('&').filter(|param| !param.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = decode(value);
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => {
+                    secret = Some(
+                        decode_secret(&value)
+                            .ok_or_else(|| invalid("the secret is not valid base32"))?,
+                    )
+                }
+                "issuer" => issuer = Some(value),
+                "algorithm" => {
+                    algorithm = match value.to_ascii_uppercase().as_str() {
+                        "SHA1" => OtpAlgorithm::Sha1,
+                        "SHA256" => OtpAlgorithm::Sha256,
+                        "SHA512" => OtpAlgorithm::Sha512,
+                        _ => return Err(invalid("the algorithm must be SHA1, SHA256 or SHA512")),
+                    }
+                }
+                "digits" => {
+                    digits = value
+                        .parse()
+                        .ok()
+                        .filter(|digits| (6..=10).contains(digits))
+                        .ok_or_else(|| invalid("digits must be a number from 6 to 10"))?
+                }
+                "period" => {
+                    period = value
+                        .parse()
+                        .ok()
+                        .filter(|&period| period > 0)
+                        .ok_or_else(|| invalid("the period must be a positive number of seconds"))?
+                }
+                "counter" => {
+                    counter = Some(
+                        value
+                            .parse()
+                            .map_err(|_| invalid("the counter must be a number"))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        let kind = match otp_type.to_ascii_lowercase().as_str() {
+            "totp" => OtpKind::Totp { period },
+            "hotp" => OtpKind::Hotp {
+                counter: counter.ok_or_else(|| invalid("HOTP requires a counter"))?,
+            },
+            _ => return Err(invalid("the type must be totp or hotp")),
+        };
+        let secret = secret
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| invalid("it has no secret"))?;
+        let issuer = issuer.or_else(|| label.split_once(':').map(|(issuer, _)| issuer.to_string()));
+
+        Ok(OtpUri {
+            kind,
+            label,
+            issuer,
+            secret,
+            algorithm,
+            digits,
+            uri: uri.to_string(),
+        })
+    }
+
+    /// Returns whether the password is time- or counter-based.
+    pub fn kind(&self) -> OtpKind {
+        self.kind
+    }
+
+    /// Returns the label, usually `issuer:account`.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the issuer, from the `issuer` parameter or the label.
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    /// Returns the hash function.
+    pub fn algorithm(&self) -> OtpAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns how many digits a code has.
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    /// Returns the current code: for TOTP the one for `unix_time` (in seconds since the Unix
+    /// epoch), for HOTP the one for the stored counter.
+    pub fn code(&self, unix_time: u64) -> String {
+        let counter = match self.kind {
+            OtpKind::Totp { period } => unix_time / period,
+            OtpKind::Hotp { counter } => counter,
+        };
+        hotp(&self.secret, self.algorithm, counter, self.digits)
+    }
+
+    /// Returns how many seconds the TOTP code for `unix_time` stays valid, or `None` for HOTP.
+    pub fn seconds_left(&self, unix_time: u64) -> Option<u64> {
+        match self.kind {
+            OtpKind::Totp { period } => Some(period - unix_time % period),
+            OtpKind::Hotp { .. } => None,
+        }
+    }
+
+    /// Moves an HOTP secret on to its next counter, so the code just used is not shown again.
+    /// The `counter` parameter of the URI is updated in place. TOTP secrets are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::InvalidInput`] if the counter is already at its largest value,
+    /// leaving the URI unchanged.
+    pub fn increment_counter(&mut self) -> Result<(), StoreError> {
+        let OtpKind::Hotp { counter } = &mut self.kind else {
+            return Ok(());
+        };
+        *counter = counter.checked_add(1).ok_or_else(|| {
+            StoreError::InvalidInput(format!(
+                "The HOTP counter of '{}' cannot be incremented past {}.",
+                self.uri,
+                u64::MAX
+            ))
+        })?;
+
+        let (base, query) = self.uri.split_once('?').unwrap_or((&self.uri, ""));
+        let params: Vec<String> = query
+            .split('&')
+            .map(|param| match param.split_once('=') {
+                Some((key, _)) if key.eq_ignore_ascii_case("counter") => {
+                    format!("{}={}", key, counter)
+                }
+                _ => param.to_string(),
+            })
+            .collect();
+        self.uri = format!("{}?{}", base, params.join("&"));
+        Ok(())
+    }
+}
+
+impl fmt::Display for OtpUri {
+    /// Writes the URI, as it was parsed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.uri)
+    }
+}
+
+/// Finds the first `otpauth://` URI in `entry`, which may be on any line, including the
+/// password line.
+///
+/// # Returns
+///
+/// The line number (counting from 1, see [`PasswordEntry::line`]) and the URI, or `None` if
+/// the entry has none.
+pub fn find_uri(entry: &PasswordEntry) -> Option<(usize, &str)> {
+    (1..)
+        .map_while(|number| entry.line(number).map(|line| (number, line.trim())))
+        .find(|(_, line)| {
+            line.get(..OTPAUTH_SCHEME.len())
+                .is_some_and(|scheme| scheme.eq_ignore_ascii_case(OTPAUTH_SCHEME))
+        })
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+///
+/// # Errors
+///
+/// Returns [`StoreError::InvalidInput`] if the system clock is set before 1970.
+pub fn unix_time() -> Result<u64, StoreError> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .map_err(|_| StoreError::InvalidInput("The system clock is set before 1970.".to_string()))
+}
+
+/// Computes the HOTP code (RFC 4226) for `counter`. TOTP (RFC 6238) is HOTP with the number
+/// of periods since the Unix epoch as the counter.
+fn hotp(secret: &[u8], algorithm: OtpAlgorithm, counter: u64, digits: u32) -> String {
+    let hash = match algorithm {
+        OtpAlgorithm::Sha1 => hmac::<Sha1>(secret, &counter.to_be_bytes()),
+        OtpAlgorithm::Sha256 => hmac::<Sha256>(secret, &counter.to_be_bytes()),
+        OtpAlgorithm::Sha512 => hmac::<Sha512>(secret, &counter.to_be_bytes()),
+    };
+
+    // Dynamic truncation: 31 bits starting at the offset given by the low nibble of the
+    // last byte.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bytes: [u8; 4] = hash[offset..offset + 4].try_into().expect("4 bytes");
+    let value = u64::from(u32::from_be_bytes(bytes) & 0x7fff_ffff);
+    format!(
+        "{:0width$}",
+        value % 10u64.pow(digits),
+        width = digits as usize
+    )
+}
+
+/// Returns the HMAC of `message` with `key`, using the hash function `D`.
+fn hmac<D>(key: &[u8], message: &[u8]) -> Vec<u8>
+where
+    D: Digest + BlockSizeUser + FixedOutputReset,
+{
+    let mut mac =
+        <SimpleHmac<D> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Decodes a base32 secret, ignoring case, spaces and `=` padding.
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    // Some issuers leave stray bits after the last byte, which a strict decoder rejects.
+    let mut spec = BASE32_NOPAD.specification();
+    spec.check_trailing_bits = false;
+    spec.encoding().ok()?.decode(normalized.as_bytes()).ok()
+}
+
+/// Decodes the percent-escapes in a part of a URI.
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The HMAC-SHA-1 secret of RFC 4226 and RFC 6238.
+    const SHA1_SEED: &[u8] = b"12345678901234567890";
+    /// The HMAC-SHA-256 secret of RFC 6238.
+    const SHA256_SEED: &[u8] = b"12345678901234567890123456789012";
+    /// The HMAC-SHA-512 secret of RFC 6238.
+    const SHA512_SEED: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    /// Parses an `otpauth://` URI of `otp_type` with `seed` as its secret and `params` appended.
+    fn uri(otp_type: &str, seed: &[u8], params: &str) -> OtpUri {
+        OtpUri::parse(&format!(
+            "otpauth://{}/Example:alice?secret={}{}",
+            otp_type,
+            BASE32_NOPAD.encode(seed),
+            params
+        ))
+        .unwrap()
+    }
+
+    /// Returns the message of the error from parsing `uri`, which must be invalid.
+    fn parse_error(uri: &str) -> String {
+        match OtpUri::parse(uri) {
+            Err(StoreError::InvalidInput(message)) => message,
+            other => panic!("{} should be invalid, got {:?}", uri, other),
+        }
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226_appendix_d() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            let uri = uri("hotp", SHA1_SEED, &format!("&counter={}", counter));
+            assert_eq!(uri.code(0), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238_appendix_b() {
+        let expected = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        let sha1 = uri("totp", SHA1_SEED, "&digits=8");
+        let sha256 = uri("totp", SHA256_SEED, "&digits=8&algorithm=SHA256");
+        let sha512 = uri("totp", SHA512_SEED, "&digits=8&algorithm=sha512");
+        for (time, sha1_code, sha256_code, sha512_code) in expected {
+            assert_eq!(sha1.code(time), sha1_code, "SHA1 at {}", time);
+            assert_eq!(sha256.code(time), sha256_code, "SHA256 at {}", time);
+            assert_eq!(sha512.code(time), sha512_code, "SHA512 at {}", time);
+        }
+    }
+
+    #[test]
+    fn totp_uses_the_period() {
+        let uri = uri("totp", SHA1_SEED, "&period=60");
+        assert_eq!(uri.kind(), OtpKind::Totp { period: 60 });
+        assert_eq!(uri.code(59), uri.code(0));
+        assert_ne!(uri.code(60), uri.code(59));
+        assert_eq!(uri.seconds_left(59), Some(1));
+    }
+
+    #[test]
+    fn parse_reads_the_parameters() {
+        let uri = OtpUri::parse(
+            " otpauth://HOTP/Big%20Bank:alice%40example.com?SECRET=gezd%20gnbv&Counter=7&digits=8 ",
+        )
+        .unwrap();
+        assert_eq!(uri.kind(), OtpKind::Hotp { counter: 7 });
+        assert_eq!(uri.label(), "Big Bank:alice@example.com");
+        assert_eq!(uri.issuer(), Some("Big Bank"));
+        assert_eq!(uri.algorithm(), OtpAlgorithm::Sha1);
+        assert_eq!(uri.digits(), 8);
+        assert_eq!(uri.seconds_left(0), None);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_uris() {
+        let cases = [
+            (
+                "https://example.com/?secret=GEZDGNBV",
+                "must start with otpauth://",
+            ),
+            (
+                "otpauth://motp/a?secret=GEZDGNBV",
+                "type must be totp or hotp",
+            ),
+            ("otpauth://totp/a", "has no secret"),
+            ("otpauth://totp/a?secret=", "has no secret"),
+            ("otpauth://totp/a?secret=not-base32!", "not valid base32"),
+            (
+                "otpauth://totp/a?secret=GEZDGNBV&algorithm=MD5",
+                "SHA1, SHA256 or SHA512",
+            ),
+            ("otpauth://totp/a?secret=GEZDGNBV&digits=5", "from 6 to 10"),
+            ("otpauth://totp/a?secret=GEZDGNBV&digits=11", "from 6 to 10"),
+            (
+                "otpauth://totp/a?secret=GEZDGNBV&digits=six",
+                "from 6 to 10",
+            ),
+            (
+                "otpauth://totp/a?secret=GEZDGNBV&period=0",
+                "positive number",
+            ),
+            ("otpauth://hotp/a?secret=GEZDGNBV", "requires a counter"),
+            (
+                "otpauth://hotp/a?secret=GEZDGNBV&counter=-1",
+                "counter must be a number",
+            ),
+        ];
+        for (uri, reason) in cases {
+            let message = parse_error(uri);
+            assert!(message.contains(reason), "{}: {}", uri, message);
+        }
+    }
+
+    #[test]
+    fn increment_counter_keeps_the_other_parameters() {
+        let written =
+            "otpauth://hotp/Example:alice?issuer=Example&Counter=41&secret=GEZDGNBV&digits=8";
+        let mut uri = OtpUri::parse(written).unwrap();
+        let before = uri.clone();
+        uri.increment_counter().unwrap();
+
+        assert_eq!(
+            uri.to_string(),
+            "otpauth://hotp/Example:alice?issuer=Example&Counter=42&secret=GEZDGNBV&digits=8"
+        );
+        assert_eq!(uri.kind(), OtpKind::Hotp { counter: 42 });
+        assert_eq!(OtpUri::parse(&uri.to_string()).unwrap(), uri);
+        assert_eq!(uri.label(), before.label());
+        assert_eq!(uri.issuer(), before.issuer());
+        assert_eq!(uri.algorithm(), before.algorithm());
+        assert_eq!(uri.digits(), before.digits());
+        assert_ne!(uri.code(0), before.code(0));
+    }
+
+    #[test]
+    fn increment_counter_leaves_totp_alone() {
+        let mut uri = uri("totp", SHA1_SEED, "");
+        let before = uri.clone();
+        uri.increment_counter().unwrap();
+        assert_eq!(uri, before);
+    }
+
+    #[test]
+    fn increment_counter_refuses_to_overflow() {
+        let written = "otpauth://hotp/a?secret=GEZDGNBV&counter=18446744073709551615";
+        let mut uri = OtpUri::parse(written).unwrap();
+        assert!(matches!(
+            uri.increment_counter(),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert_eq!(uri.to_string(), written);
+        assert_eq!(uri.kind(), OtpKind::Hotp { counter: u64::MAX });
+    }
+}
@@ -0,0 +1,884 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::{default_backend, CryptoBackend};
+use crate::entry::PasswordEntry;
+use crate::error::StoreError;
+use crate::integrations::age::{AgeBackend, AGE_RECIPIENTS_FILE};
+use crate::integrations::git::{git_add_file, git_commit_paths, git_remove_file};
+use crate::integrations::gpg::{
+    encrypt_to_temp, reencrypt_path, resolve_recipients, stale_entries, ReencryptProgress,
+    ReencryptReport,
+};
+use crate::journal::rename_synced;
+use crate::utils::check_sneaky_paths;
+
+/// A password store rooted at a directory on disk.
+///
+/// Entries are addressed by name, e.g. `"web/example.com"`, which maps to the file
+/// `<root>/web/example.com.gpg`, or `<root>/web/example.com.age` in an age store. Every
+/// method returns its result instead of printing, prompting or exiting, so the store can be
+/// embedded in other tools. Changes are committed to Git when the store is a Git repository.
+///
+/// All encryption, decryption and signature checks go through a [`CryptoBackend`], which is
+/// the `gpg` subprocess unless the store uses age or another one is given to
+/// [`PasswordStore::with_backend`].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use password_store_rs::PasswordStore;
+///
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let store = PasswordStore::open("/home/me/.password-store")?;
+/// for name in store.list()? {
+///     println!("{}", name);
+/// }
+/// store.insert("web/example.com", b"hunter2", "Add given password for web/example.com to store.")?;
+/// let secret = store.get("web/example.com")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PasswordStore {
+    root: PathBuf,
+    backend: Box<dyn CryptoBackend>,
+}
+
+impl PasswordStore {
+    /// Opens the password store at `root` with the backend it was initialized for.
+    ///
+    /// A store whose root contains a `.age-recipients` file uses [`AgeBackend`], configured
+    /// with [`AgeBackend::from_env`]. Any other store uses [`default_backend`].
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(PasswordStore)` if `root` is an existing directory.
+    /// * `Err(StoreError::NotInitialized)` otherwise.
+    pub fn open(root: impl Into<PathBuf>) -> Result<PasswordStore, StoreError> {
+        let root = root.into();
+        let backend: Box<dyn CryptoBackend> = if root.join(AGE_RECIPIENTS_FILE).is_file() {
+            Box::new(AgeBackend::from_env())
+        } else {
+            default_backend()?
+        };
+        PasswordStore::with_backend(root, backend)
+    }
+
+    /// Opens the password store at `root`, using `backend` for all cryptographic operations.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(PasswordStore)` if `root` is an existing directory.
+    /// * `Err(StoreError::NotInitialized)` otherwise.
+    pub fn with_backend(
+        root: impl Into<PathBuf>,
+        backend: Box<dyn CryptoBackend>,
+    ) -> Result<PasswordStore, StoreError> {
+        let root = root.into();
+        if !root.is_dir() {
+            return Err(StoreError::NotInitialized(root));
+        }
+        Ok(PasswordStore { root, backend })
+    }
+
+    /// Returns the root directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the backend used for encryption, decryption and signatures.
+    pub fn backend(&self) -> &dyn CryptoBackend {
+        self.backend.as_ref()
+    }
+
+    /// Returns the path of the encrypted file for the entry `name`.
+    ///
+    /// The file does not need to exist.
+    pub fn entry_path(&self, name: &str) -> PathBuf {
+        self.root
+            .join(format!("{}.{}", name, self.backend.extension()))
+    }
+
+    /// Returns the name of the entry stored in the file at `path`, the inverse of
+    /// [`PasswordStore::entry_path`].
+    ///
+    /// A path outside the store is returned as it is, without its extension.
+    pub fn entry_name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .with_extension("")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns whether an entry called `name` exists.
+    pub fn contains(&self, name: &str) -> bool {
+        !name.is_empty() && self.entry_path(name).is_file()
+    }
+
+    /// Returns whether `name` is a folder of the store.
+    pub fn is_folder(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('/');
+        !name.is_empty() && self.root.join(name).is_dir()
+    }
+
+    /// Returns the names of all entries, sorted, without the `.gpg` (or `.age`) extension.
+    ///
+    /// Hidden files and directories, such as `.git` and `.gpg-id`, are skipped.
+    pub fn list(&self) -> Result<Vec<String>, StoreError> {
+        let mut files = Vec::new();
+        collect_entries(&self.root, self.backend.extension(), &mut files)?;
+
+        let mut names: Vec<String> = files
+            .iter()
+            .filter_map(|file| file.strip_prefix(&self.root).ok())
+            .map(|relative| relative.with_extension("").to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the recipients that the entry `name` is (or would be) encrypted for.
+    ///
+    /// These come from the nearest `.gpg-id` (or `.age-recipients`) between the entry's folder
+    /// and the store root.
+    pub fn recipients(&self, name: &str) -> Result<Vec<String>, StoreError> {
+        check_sneaky_paths(vec![name])?;
+        resolve_recipients(self.backend(), &self.root, &self.entry_path(name))
+    }
+
+    /// Decrypts and returns the contents of the entry `name`.
+    pub fn get(&self, name: &str) -> Result<Vec<u8>, StoreError> {
+        check_sneaky_paths(vec![name])?;
+        if !self.contains(name) {
+            return Err(StoreError::EntryNotFound(name.to_string()));
+        }
+        self.backend.decrypt(&self.entry_path(name))
+    }
+
+    /// Decrypts the entry `name` and parses it into its password, fields and notes (see
+    /// [`PasswordEntry`]).
+    ///
+    /// Contents that are not valid UTF-8 are read with replacement characters.
+    pub fn entry(&self, name: &str) -> Result<PasswordEntry, StoreError> {
+        let contents = self.get(name)?;
+        Ok(PasswordEntry::parse(&String::from_utf8_lossy(&contents)))
+    }
+
+    /// Encrypts `contents` as the entry `name` and commits it with `message`.
+    ///
+    /// An existing entry is overwritten. Missing folders are created, and the entry is
+    /// encrypted for the recipients returned by [`PasswordStore::recipients`].
+    pub fn insert(&self, name: &str, contents: &[u8], message: &str) -> Result<(), StoreError> {
+        let recipients = self.recipients(name)?;
+        let passfile = self.entry_path(name);
+
+        if let Some(parent) = passfile.parent() {
+            create_dir_all(parent)?;
+        }
+
+        self.backend.encrypt(contents, &recipients, &passfile)?;
+        git_add_file(&passfile.to_string_lossy(), message)?;
+        Ok(())
+    }
+
+    /// Removes the entry `name`, or the folder `name` when `recursive` is set, and commits the removal.
+    ///
+    /// Folders left empty by the removal are deleted as well. Anything that does not
+    /// resolve to a location strictly inside the store is refused.
+    pub fn remove(&self, name: &str, recursive: bool) -> Result<(), StoreError> {
+        check_sneaky_paths(vec![name])?;
+
+        let target = if self.contains(name) && !name.ends_with('/') {
+            self.entry_path(name)
+        } else if self.is_folder(name) {
+            if !recursive {
+                return Err(StoreError::IsDirectory(name.to_string()));
+            }
+            self.root.join(name.trim_end_matches('/'))
+        } else {
+            return Err(StoreError::EntryNotFound(name.to_string()));
+        };
+
+        if !self.is_inside(&target) {
+            return Err(StoreError::OutsideStore(target));
+        }
+
+        remove_path(&target)?;
+        git_remove_file(
+            &target.to_string_lossy(),
+            &format!("Remove {} from store.", name),
+        )?;
+
+        self.remove_empty_parents(&target);
+        Ok(())
+    }
+
+    /// Returns whether moving or copying `old` to `new` would replace something that exists.
+    pub fn destination_exists(&self, old: &str, new: &str) -> Result<bool, StoreError> {
+        let (_, destination) = self.transfer_paths(old, new)?;
+        Ok(destination.exists())
+    }
+
+    /// Moves the entry or folder `old` to `new` and commits the rename.
+    ///
+    /// See [`PasswordStore::copy`] for how `new` is interpreted and when entries are re-encrypted.
+    pub fn rename(&self, old: &str, new: &str, overwrite: bool) -> Result<(), StoreError> {
+        self.transfer(old, new, overwrite, true)
+    }
+
+    /// Copies the entry or folder `old` to `new` and commits the copy.
+    ///
+    /// If `new` is an existing folder or ends with `/`, `old` is placed inside it under its
+    /// own name. An existing destination is only replaced when `overwrite` is set. Every
+    /// entry whose governing `.gpg-id` differs between its old and new location is
+    /// re-encrypted for the new recipients.
+    pub fn copy(&self, old: &str, new: &str, overwrite: bool) -> Result<(), StoreError> {
+        self.transfer(old, new, overwrite, false)
+    }
+
+    /// Re-encrypts every entry in `subfolder` (the whole store if empty) for its current recipients.
+    ///
+    /// Entries that are already encrypted for those recipients' keys, and nested folders that
+    /// have their own `.gpg-id` (or `.age-recipients`), are left alone. Entries are re-encrypted
+    /// in parallel, and `progress` is called after each one. An entry that fails does not stop
+    /// the others; it is listed in the returned report (see [`reencrypt_path`]).
+    pub fn reencrypt(
+        &self,
+        subfolder: &str,
+        progress: &(dyn Fn(ReencryptProgress) + Sync),
+    ) -> Result<ReencryptReport, StoreError> {
+        check_sneaky_paths(vec![subfolder])?;
+        reencrypt_path(
+            self.backend(),
+            &self.root,
+            &self.root.join(subfolder),
+            progress,
+        )
+    }
+
+    /// Returns the names of the entries in `subfolder` (the whole store if empty) that
+    /// [`PasswordStore::reencrypt`] would rewrite if the folder were encrypted for `recipients`.
+    ///
+    /// Nothing is changed, so this can report what `init` would do before the recipients file
+    /// is written (see [`stale_entries`]).
+    pub fn stale_entries(
+        &self,
+        subfolder: &str,
+        recipients: &[String],
+    ) -> Result<Vec<String>, StoreError> {
+        check_sneaky_paths(vec![subfolder])?;
+        Ok(
+            stale_entries(self.backend(), &self.root.join(subfolder), recipients)?
+                .iter()
+                .map(|file| self.entry_name(file))
+                .collect(),
+        )
+    }
+
+    /// Resolves the source and destination paths of a move or copy.
+    fn transfer_paths(&self, old: &str, new: &str) -> Result<(PathBuf, PathBuf), StoreError> {
+        check_sneaky_paths(vec![old, new])?;
+
+        let source = if self.contains(old) && !old.ends_with('/') {
+            self.entry_path(old)
+        } else if self.is_folder(old) {
+            self.root.join(old.trim_end_matches('/'))
+        } else {
+            return Err(StoreError::EntryNotFound(old.to_string()));
+        };
+
+        let new_base = self.root.join(new.trim_end_matches('/'));
+        let destination = if new.is_empty() || new.ends_with('/') || new_base.is_dir() {
+            new_base.join(source.file_name().expect("source has a file name"))
+        } else if source.is_dir() {
+            new_base
+        } else {
+            self.entry_path(new)
+        };
+
+        Ok((source, destination))
+    }
+
+    /// Moves (`move_entry == true`) or copies `old` to `new`, re-encrypting as needed.
+    ///
+    /// The source is first moved or copied to a hidden staging path next to the destination,
+    /// and its entries are re-encrypted there (see [`PasswordStore::reencrypt_staged`]). Only
+    /// once that has succeeded is an existing destination moved aside and the staged copy
+    /// renamed into its place. If anything fails before that, the staged copy is moved back
+    /// or removed, so the source and the destination are left as they were.
+    fn transfer(
+        &self,
+        old: &str,
+        new: &str,
+        overwrite: bool,
+        move_entry: bool,
+    ) -> Result<(), StoreError> {
+        let verb = if move_entry { "move" } else { "copy" };
+        let (source, destination) = self.transfer_paths(old, new)?;
+
+        if destination == source || destination.starts_with(&source) {
+            return Err(StoreError::InvalidInput(format!(
+                "cannot {} {} into itself.",
+                verb, old
+            )));
+        }
+
+        // Remember who every entry is encrypted for before anything moves.
+        let recipients_before = self.recipients_by_entry(&source, &source)?;
+
+        if destination.exists() && !overwrite {
+            return Err(StoreError::EntryExists(new.to_string()));
+        }
+
+        if let Some(parent) = destination.parent() {
+            create_dir_all(parent)?;
+        }
+
+        // Leftovers of an interrupted move may hold the only copy of its entries.
+        let staging = hidden_sibling(&destination, "transfer-tmp");
+        let replaced = hidden_sibling(&destination, "transfer-old");
+        if let Some(leftover) = [&staging, &replaced]
+            .into_iter()
+            .find(|path| fs::symlink_metadata(path).is_ok())
+        {
+            return Err(StoreError::InvalidInput(format!(
+                "{} is left over from an interrupted move or copy. Move its contents back or \
+                 remove it, then try again.",
+                leftover.display()
+            )));
+        }
+
+        let staged = if move_entry {
+            fs::rename(&source, &staging)
+        } else {
+            copy_recursive(&source, &staging)
+        };
+        if let Err(e) = staged {
+            if !move_entry {
+                let _ = remove_path(&staging);
+            }
+            self.remove_empty_parents(&staging);
+            return Err(StoreError::io(
+                format!(
+                    "failed to {} {} to {}",
+                    verb,
+                    source.display(),
+                    destination.display()
+                ),
+                e,
+            ));
+        }
+
+        // An existing destination is moved aside rather than removed, so it can be put back.
+        let swapped = self
+            .reencrypt_staged(&staging, recipients_before)
+            .and_then(|_| {
+                if destination.exists() {
+                    rename_synced(&destination, &replaced)?;
+                }
+                rename_synced(&staging, &destination).inspect_err(|_| {
+                    if replaced.exists() {
+                        let _ = fs::rename(&replaced, &destination);
+                    }
+                })
+            });
+        if let Err(e) = swapped {
+            if move_entry {
+                let _ = fs::rename(&staging, &source);
+            } else {
+                let _ = remove_path(&staging);
+            }
+            self.remove_empty_parents(&staging);
+            return Err(e);
+        }
+        if replaced.exists() {
+            remove_path(&replaced)?;
+        }
+
+        let (paths, message) = if move_entry {
+            (
+                vec![source.to_string_lossy(), destination.to_string_lossy()],
+                format!("Rename {} to {}.", old, new),
+            )
+        } else {
+            (
+                vec![destination.to_string_lossy()],
+                format!("Copy {} to {}.", old, new),
+            )
+        };
+        let paths: Vec<&str> = paths.iter().map(|p| p.as_ref()).collect();
+        git_commit_paths(&self.root, &paths, &message)?;
+
+        if move_entry {
+            self.remove_empty_parents(&source);
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts the entries of `staging`, a moved or copied source, whose recipients at
+    /// their new location differ from `recipients_before` (see
+    /// [`PasswordStore::recipients_by_entry`]).
+    ///
+    /// `staging` sits next to the destination, so its entries resolve to the recipients they
+    /// will have there. Every entry is first encrypted into a temporary file, and the
+    /// temporary files are renamed over the entries only once all of them have succeeded.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once every entry is encrypted for its new recipients.
+    /// * `Err(StoreError)` if the recipients cannot be resolved or an entry cannot be
+    ///   re-encrypted. The temporary files are removed and the entries are left untouched.
+    fn reencrypt_staged(
+        &self,
+        staging: &Path,
+        recipients_before: Vec<(PathBuf, Vec<String>)>,
+    ) -> Result<(), StoreError> {
+        let mut temps = Vec::new();
+        for (relative, before) in recipients_before {
+            let entry = if relative.as_os_str().is_empty() {
+                staging.to_path_buf()
+            } else {
+                staging.join(&relative)
+            };
+            let encrypted =
+                resolve_recipients(self.backend(), &self.root, &entry).and_then(|after| {
+                    if after == before {
+                        Ok(None)
+                    } else {
+                        encrypt_to_temp(self.backend(), &entry, &after).map(Some)
+                    }
+                });
+            match encrypted {
+                Ok(Some(temp)) => temps.push((temp, entry)),
+                Ok(None) => {}
+                Err(e) => {
+                    for (temp, _) in temps {
+                        let _ = fs::remove_file(temp);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        for (temp, entry) in temps {
+            rename_synced(&temp, &entry)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the recipients every entry under `path` is currently encrypted for.
+    ///
+    /// Each entry is paired with its path relative to `root`, so that the same entry can be
+    /// found again after it has been moved or copied.
+    fn recipients_by_entry(
+        &self,
+        root: &Path,
+        path: &Path,
+    ) -> Result<Vec<(PathBuf, Vec<String>)>, StoreError> {
+        let mut recipients = Vec::new();
+
+        if path.is_dir() {
+            let entries = read_dir(path)?;
+            for entry in entries.flatten() {
+                recipients.extend(self.recipients_by_entry(root, &entry.path())?);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == self.backend.extension())
+        {
+            let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+            recipients.push((
+                relative,
+                resolve_recipients(self.backend(), &self.root, path)?,
+            ));
+        }
+
+        Ok(recipients)
+    }
+
+    /// Returns whether `path` resolves to a location strictly inside the store.
+    ///
+    /// The parent directory is canonicalized rather than `path` itself, so that a symlinked
+    /// entry is judged by where the link lives and not by where it points.
+    fn is_inside(&self, path: &Path) -> bool {
+        let (Ok(store), Some(parent), Some(name)) = (
+            fs::canonicalize(&self.root),
+            path.parent(),
+            path.file_name(),
+        ) else {
+            return false;
+        };
+        if name == "." || name == ".." {
+            return false;
+        }
+
+        match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(name).starts_with(&store) && parent.join(name) != store,
+            Err(_) => false,
+        }
+    }
+
+    /// Removes the now-empty parent directories of `path`, stopping at the store root.
+    ///
+    /// Directories that still contain anything (including hidden files such as `.gpg-id`)
+    /// are left in place.
+    fn remove_empty_parents(&self, path: &Path) {
+        let mut current = path.parent();
+
+        while let Some(dir) = current {
+            if dir == self.root || !dir.starts_with(&self.root) {
+                break;
+            }
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+            current = dir.parent();
+        }
+    }
+}
+
+/// Recursively collects the files with `extension` under `dir`, skipping hidden files and
+/// directories.
+fn collect_entries(
+    dir: &Path,
+    extension: &str,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), StoreError> {
+    let entries = read_dir(dir)?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_entries(&path, extension, files)?;
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the directory `dir`.
+fn read_dir(dir: &Path) -> Result<fs::ReadDir, StoreError> {
+    fs::read_dir(dir)
+        .map_err(|e| StoreError::io(format!("Failed to read directory {}", dir.display()), e))
+}
+
+/// Creates the directory `dir` and any missing parents.
+fn create_dir_all(dir: &Path) -> Result<(), StoreError> {
+    fs::create_dir_all(dir)
+        .map_err(|e| StoreError::io(format!("Error creating directory {}", dir.display()), e))
+}
+
+/// Removes the file, or the directory and all of its contents, at `path`.
+fn remove_path(path: &Path) -> Result<(), StoreError> {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    removed.map_err(|e| StoreError::io(format!("Error removing {}", path.display()), e))
+}
+
+/// Returns the hidden path next to `path` that a move or copy uses while it is in progress,
+/// e.g. `/store/web/.example.com.gpg.transfer-tmp` for `/store/web/example.com.gpg`.
+fn hidden_sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Copies a file, or a directory and all of its contents, from `from` to `to`.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::fake::FakeBackend;
+
+    /// Creates a store for the test `name` whose root is encrypted for alice and whose
+    /// `work` folder is encrypted for bob. The keyring holds alice, bob and carol.
+    fn store(name: &str) -> PasswordStore {
+        let root = std::env::temp_dir().join(format!(
+            "password-store-rs-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("work")).unwrap();
+        fs::write(root.join(".gpg-id"), "alice\n").unwrap();
+        fs::write(root.join("work/.gpg-id"), "bob\n").unwrap();
+        let backend = FakeBackend::new(&["alice", "bob", "carol"]);
+        PasswordStore::with_backend(root, Box::new(backend)).unwrap()
+    }
+
+    /// Returns who the entry `name` is encrypted for, and its contents.
+    fn read(store: &PasswordStore, name: &str) -> (Vec<String>, String) {
+        let (recipients, contents) = FakeBackend::read(&store.entry_path(name)).unwrap();
+        (recipients, String::from_utf8(contents).unwrap())
+    }
+
+    fn encrypted_for(recipients: &[&str], contents: &str) -> (Vec<String>, String) {
+        (
+            recipients.iter().map(|r| r.to_string()).collect(),
+            contents.to_string(),
+        )
+    }
+
+    /// Returns the hidden files a move or copy left in `dir`.
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".transfer-tmp") || name.ends_with(".transfer-old"))
+            .collect()
+    }
+
+    #[test]
+    fn insert_encrypts_for_the_nearest_gpg_id() {
+        let store = store("insert");
+        store.insert("web/example.com", b"hunter2", "").unwrap();
+        store.insert("work/vpn", b"s3cret", "").unwrap();
+
+        assert_eq!(store.get("web/example.com").unwrap(), b"hunter2");
+        assert_eq!(
+            read(&store, "web/example.com"),
+            encrypted_for(&["alice"], "hunter2")
+        );
+        assert_eq!(read(&store, "work/vpn"), encrypted_for(&["bob"], "s3cret"));
+        assert_eq!(store.list().unwrap(), ["web/example.com", "work/vpn"]);
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn insert_fails_without_a_gpg_id() {
+        let store = store("no-gpg-id");
+        fs::remove_file(store.root().join(".gpg-id")).unwrap();
+
+        let error = store.insert("web/example.com", b"hunter2", "").err();
+        assert!(
+            matches!(error, Some(StoreError::RecipientMissing(_))),
+            "{:?}",
+            error
+        );
+        assert!(!store.contains("web/example.com"));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn rename_reencrypts_for_the_destination() {
+        let store = store("rename");
+        store.insert("email", b"hunter2", "").unwrap();
+
+        store.rename("email", "work/", false).unwrap();
+
+        assert!(!store.contains("email"));
+        assert_eq!(
+            read(&store, "work/email"),
+            encrypted_for(&["bob"], "hunter2")
+        );
+        assert!(leftovers(&store.root().join("work")).is_empty());
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn copy_keeps_the_source_as_it_was() {
+        let store = store("copy");
+        store.insert("web/example.com", b"hunter2", "").unwrap();
+
+        store.copy("web", "work/web", false).unwrap();
+
+        assert_eq!(
+            read(&store, "web/example.com"),
+            encrypted_for(&["alice"], "hunter2")
+        );
+        assert_eq!(
+            read(&store, "work/web/example.com"),
+            encrypted_for(&["bob"], "hunter2")
+        );
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn a_folder_with_its_own_gpg_id_keeps_its_recipients() {
+        let store = store("own-gpg-id");
+        store.insert("work/vpn", b"s3cret", "").unwrap();
+
+        store.rename("work", "jobs", false).unwrap();
+
+        assert_eq!(read(&store, "jobs/vpn"), encrypted_for(&["bob"], "s3cret"));
+        assert!(!store.is_folder("work"));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn an_existing_destination_is_only_replaced_with_overwrite() {
+        let store = store("overwrite");
+        store.insert("email", b"new", "").unwrap();
+        store.insert("work/email", b"old", "").unwrap();
+
+        let error = store.rename("email", "work/email", false).err();
+        assert!(
+            matches!(&error, Some(StoreError::EntryExists(name)) if name == "work/email"),
+            "{:?}",
+            error
+        );
+        assert_eq!(read(&store, "work/email"), encrypted_for(&["bob"], "old"));
+
+        store.rename("email", "work/email", true).unwrap();
+        assert!(!store.contains("email"));
+        assert_eq!(read(&store, "work/email"), encrypted_for(&["bob"], "new"));
+        assert!(leftovers(&store.root().join("work")).is_empty());
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn a_failed_reencryption_leaves_source_and_destination_alone() {
+        let store = store("failed-transfer");
+        store.insert("web/a", b"a", "").unwrap();
+        store.insert("web/b", b"b", "").unwrap();
+        store.insert("shared/web/a", b"old a", "").unwrap();
+        // Nobody is not in the keyring, so web/b cannot be re-encrypted for shared/.
+        fs::write(store.root().join("shared/.gpg-id"), "carol\nnobody\n").unwrap();
+
+        for move_entry in [true, false] {
+            let error = store.transfer("web", "shared/", true, move_entry).err();
+            assert!(
+                matches!(error, Some(StoreError::RecipientMissing(_))),
+                "{:?}",
+                error
+            );
+
+            assert_eq!(read(&store, "web/a"), encrypted_for(&["alice"], "a"));
+            assert_eq!(read(&store, "web/b"), encrypted_for(&["alice"], "b"));
+            assert_eq!(
+                read(&store, "shared/web/a"),
+                encrypted_for(&["alice"], "old a")
+            );
+            assert!(!store.contains("shared/web/b"));
+            assert!(leftovers(&store.root().join("shared")).is_empty());
+        }
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn leftovers_of_an_interrupted_move_are_not_overwritten() {
+        let store = store("leftover");
+        store.insert("email", b"hunter2", "").unwrap();
+        let staging = store.root().join("work/.email.gpg.transfer-tmp");
+        fs::write(&staging, "the only copy").unwrap();
+
+        let error = store.rename("email", "work/", false).err();
+        assert!(
+            matches!(&error, Some(StoreError::InvalidInput(message)) if message.contains("interrupted")),
+            "{:?}",
+            error
+        );
+        assert!(store.contains("email"));
+        assert_eq!(fs::read_to_string(&staging).unwrap(), "the only copy");
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn a_folder_cannot_be_moved_into_itself() {
+        let store = store("into-itself");
+        store.insert("web/example.com", b"hunter2", "").unwrap();
+
+        let error = store.rename("web", "web/old", false).err();
+        assert!(
+            matches!(error, Some(StoreError::InvalidInput(_))),
+            "{:?}",
+            error
+        );
+        assert!(store.contains("web/example.com"));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn reencrypt_only_rewrites_stale_entries() {
+        let store = store("reencrypt");
+        store.insert("email", b"hunter2", "").unwrap();
+        store.insert("web/example.com", b"letmein", "").unwrap();
+        store.insert("work/vpn", b"s3cret", "").unwrap();
+        fs::write(store.root().join(".gpg-id"), "alice\ncarol\n").unwrap();
+        // Already encrypted for alice and carol, in another order.
+        store
+            .backend()
+            .encrypt(
+                b"letmein",
+                &["carol".to_string(), "alice".to_string()],
+                &store.entry_path("web/example.com"),
+            )
+            .unwrap();
+
+        let report = store.reencrypt("", &|_| {}).unwrap();
+
+        assert_eq!(report.reencrypted, [store.entry_path("email")]);
+        assert_eq!(report.skipped, 1);
+        assert!(report.failed.is_empty());
+        assert_eq!(
+            read(&store, "email"),
+            encrypted_for(&["alice", "carol"], "hunter2")
+        );
+        assert_eq!(read(&store, "work/vpn"), encrypted_for(&["bob"], "s3cret"));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn reencrypt_reports_entries_that_fail() {
+        let store = store("reencrypt-failed");
+        store.insert("email", b"hunter2", "").unwrap();
+        fs::write(store.root().join("broken.gpg"), "not encrypted").unwrap();
+        fs::write(store.root().join(".gpg-id"), "carol\n").unwrap();
+
+        let report = store.reencrypt("", &|_| {}).unwrap();
+
+        assert_eq!(report.reencrypted, [store.entry_path("email")]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, store.entry_path("broken"));
+        assert_eq!(read(&store, "email"), encrypted_for(&["carol"], "hunter2"));
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn stale_entries_lists_what_reencrypt_would_rewrite() {
+        let store = store("stale");
+        store.insert("email", b"hunter2", "").unwrap();
+        store.insert("web/example.com", b"letmein", "").unwrap();
+        store.insert("work/vpn", b"s3cret", "").unwrap();
+
+        let alice = vec!["alice".to_string()];
+        let carol = vec!["carol".to_string()];
+        assert!(store.stale_entries("", &alice).unwrap().is_empty());
+        let mut stale = store.stale_entries("", &carol).unwrap();
+        stale.sort();
+        assert_eq!(stale, ["email", "web/example.com"]);
+        assert_eq!(store.stale_entries("work", &carol).unwrap(), ["work/vpn"]);
+        assert_eq!(read(&store, "email"), encrypted_for(&["alice"], "hunter2"));
+
+        let error = store.stale_entries("../elsewhere", &carol).err();
+        assert!(
+            matches!(error, Some(StoreError::SneakyPath(_))),
+            "{:?}",
+            error
+        );
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+}
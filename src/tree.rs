@@ -0,0 +1,338 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::StoreError;
+
+/// The extensions that are stripped from the names of entries when they are listed.
+const ENTRY_EXTENSIONS: [&str; 2] = [".gpg", ".age"];
+
+/// A file or folder in a listing of the password store, as built by [`walk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    /// The name shown for the node: the file name without its `.gpg` or `.age` extension.
+    /// Names that are not valid UTF-8 are shown with replacement characters.
+    pub name: String,
+    /// Whether the node is a folder.
+    pub is_dir: bool,
+    /// Where the node points to, if it is a symlink.
+    pub link_target: Option<PathBuf>,
+    /// The files and folders in the folder, sorted alphabetically. Empty for files.
+    pub children: Vec<TreeNode>,
+}
+
+/// Matches file and folder names against the search terms of `find`.
+///
+/// A term matches any name that contains it, ignoring case. Terms may use the glob wildcards
+/// `*`, `?` and `[...]` (`[!...]` to negate), like the patterns of `tree -P`: `bank*us`
+/// matches `my-bank-us`. A name matches if any term matches it.
+///
+/// # Examples
+///
+/// ```rust
+/// # use password_store_rs::tree::NameMatcher;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// let matcher = NameMatcher::new(&["email", "bank*us"])?;
+/// assert!(matcher.is_match("Work-Email"));
+/// assert!(matcher.is_match("bank-of-us"));
+/// assert!(!matcher.is_match("example.com"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NameMatcher {
+    /// One case-insensitive regex per term.
+    patterns: Vec<Regex>,
+}
+
+impl NameMatcher {
+    /// Creates a matcher for `terms`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NameMatcher)` that matches names containing any of `terms`.
+    /// * `Err(StoreError::InvalidInput)` if a term cannot be turned into a pattern.
+    pub fn new(terms: &[&str]) -> Result<NameMatcher, StoreError> {
+        let patterns = terms
+            .iter()
+            .map(|term| {
+                RegexBuilder::new(&glob_to_regex(term))
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| {
+                        StoreError::InvalidInput(format!("invalid pattern '{}': {}", term, e))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(NameMatcher { patterns })
+    }
+
+    /// Returns whether any term matches `name`.
+    pub fn is_match(&self, name: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(name))
+    }
+}
+
+/// Builds the tree of the files and folders under `dir`, sorted alphabetically by name,
+/// ignoring case.
+///
+/// Hidden files and folders, such as `.git` and `.gpg-id`, are left out, and so are folders
+/// that contain nothing else. Symlinks are followed and their targets recorded, but a folder
+/// that links back to one of its own parents is listed without its contents instead of being
+/// walked forever.
+///
+/// With a `matcher`, only files whose name matches it are kept, and folders that end up
+/// empty are pruned. A folder whose own name matches is kept with everything in it, like
+/// `tree --prune --matchdirs`.
+///
+/// # Arguments
+///
+/// * `dir` - The folder to list, usually the root of the store.
+/// * `matcher` - The search terms to keep files and folders by, or `None` to keep all.
+///
+/// # Returns
+///
+/// * `Ok(Vec<TreeNode>)` with the contents of `dir`. A missing `dir` has none.
+/// * `Err(StoreError::Io)` if a folder cannot be read.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::tree::{render, walk, NameMatcher};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// # let store = password_store_rs::PasswordStore::open("/home/me/.password-store")?;
+/// let matcher = NameMatcher::new(&["bank"])?;
+/// print!("{}", render(&walk(store.root(), Some(&matcher))?, false, None));
+/// # Ok(())
+/// # }
+/// ```
+pub fn walk(dir: &Path, matcher: Option<&NameMatcher>) -> Result<Vec<TreeNode>, StoreError> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut ancestors = Vec::new();
+    if let Ok(canonical) = dir.canonicalize() {
+        ancestors.push(canonical);
+    }
+    walk_dir(dir, matcher, &mut ancestors)
+}
+
+/// Renders `nodes` as a tree with box-drawing connectors, one line per node:
+///
+/// ```text
+/// ├── email
+/// │   └── work
+/// └── example.com
+/// ```
+///
+/// A symlink is shown as `name -> target`. With `color`, folder names are shown in bold blue
+/// and symlinks in bold cyan, like `tree -C`. With `max_depth`, only that many levels are
+/// shown: `Some(1)` shows the top-level entries and folders without their contents.
+pub fn render(nodes: &[TreeNode], color: bool, max_depth: Option<usize>) -> String {
+    let mut out = String::new();
+    render_into(nodes, "", color, max_depth, &mut out);
+    out
+}
+
+/// Returns the path of every entry in `nodes`, such as `email/work`, in the order they are
+/// rendered.
+///
+/// Each path starts with `prefix`, which is joined with a `/` unless it is empty. Folders are
+/// not listed themselves. With `max_depth`, only the entries within that many levels are
+/// included: `Some(1)` gives the top-level entries.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::tree::{entry_paths, walk};
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// # let store = password_store_rs::PasswordStore::open("/home/me/.password-store")?;
+/// let nodes = walk(&store.root().join("email"), None)?;
+/// assert_eq!(entry_paths(&nodes, "email", None), ["email/personal", "email/work"]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn entry_paths(nodes: &[TreeNode], prefix: &str, max_depth: Option<usize>) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_paths(nodes, prefix, max_depth, &mut paths);
+    paths
+}
+
+/// Walks `dir` for [`walk`]. `ancestors` holds the canonical paths of the folders being
+/// walked, from the root down to `dir`.
+fn walk_dir(
+    dir: &Path,
+    matcher: Option<&NameMatcher>,
+    ancestors: &mut Vec<PathBuf>,
+) -> Result<Vec<TreeNode>, StoreError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| StoreError::io(format!("Failed to read directory {}", dir.display()), e))?;
+
+    let mut nodes = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| StoreError::io("Failed to read directory entry", e))?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let link_target = entry
+            .file_type()
+            .is_ok_and(|file_type| file_type.is_symlink())
+            .then(|| fs::read_link(&path).ok())
+            .flatten();
+
+        // `is_dir` follows symlinks; a broken symlink is listed like a file.
+        if !path.is_dir() {
+            let name = entry_name(&file_name);
+            if matcher.is_none_or(|matcher| matcher.is_match(&name)) {
+                nodes.push(TreeNode {
+                    name,
+                    is_dir: false,
+                    link_target,
+                    children: Vec::new(),
+                });
+            }
+            continue;
+        }
+
+        let matches_dir = matcher.is_some_and(|matcher| matcher.is_match(&file_name));
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let looped = ancestors.contains(&canonical);
+        let children = if looped {
+            Vec::new()
+        } else {
+            ancestors.push(canonical);
+            let children = walk_dir(&path, matcher.filter(|_| !matches_dir), ancestors);
+            ancestors.pop();
+            children?
+        };
+
+        // A looping symlink is still listed when nothing is filtered, so it can be seen.
+        let keep = match matcher {
+            None => looped || !children.is_empty(),
+            Some(_) => matches_dir || !children.is_empty(),
+        };
+        if keep {
+            nodes.push(TreeNode {
+                name: file_name,
+                is_dir: true,
+                link_target,
+                children,
+            });
+        }
+    }
+
+    nodes.sort_by(|a, b| (a.name.to_lowercase(), &a.name).cmp(&(b.name.to_lowercase(), &b.name)));
+    Ok(nodes)
+}
+
+/// Appends the lines for `nodes` to `out`, each starting with `prefix`, and at most
+/// `max_depth` levels of them.
+fn render_into(
+    nodes: &[TreeNode],
+    prefix: &str,
+    color: bool,
+    max_depth: Option<usize>,
+    out: &mut String,
+) {
+    if max_depth == Some(0) {
+        return;
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        let last = i + 1 == nodes.len();
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        let style = match (node.link_target.is_some(), node.is_dir) {
+            (true, _) => Some("1;36"),
+            (false, true) => Some("1;34"),
+            (false, false) => None,
+        };
+        match style.filter(|_| color) {
+            Some(style) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", style, node.name)),
+            None => out.push_str(&node.name),
+        }
+        if let Some(target) = &node.link_target {
+            out.push_str(&format!(" -> {}", target.to_string_lossy()));
+        }
+        out.push('\n');
+
+        if node.is_dir {
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            let depth = max_depth.map(|depth| depth - 1);
+            render_into(&node.children, &child_prefix, color, depth, out);
+        }
+    }
+}
+
+/// Appends the paths of the entries in `nodes` to `paths` for [`entry_paths`].
+fn collect_paths(
+    nodes: &[TreeNode],
+    prefix: &str,
+    max_depth: Option<usize>,
+    paths: &mut Vec<String>,
+) {
+    if max_depth == Some(0) {
+        return;
+    }
+    for node in nodes {
+        let path = if prefix.is_empty() {
+            node.name.clone()
+        } else {
+            format!("{}/{}", prefix, node.name)
+        };
+        if node.is_dir {
+            let depth = max_depth.map(|depth| depth - 1);
+            collect_paths(&node.children, &path, depth, paths);
+        } else {
+            paths.push(path);
+        }
+    }
+}
+
+/// Returns `file_name` without its `.gpg` or `.age` extension.
+fn entry_name(file_name: &str) -> String {
+    ENTRY_EXTENSIONS
+        .iter()
+        .find_map(|extension| file_name.strip_suffix(extension))
+        .unwrap_or(file_name)
+        .to_string()
+}
+
+/// Translates the glob `term` into a regex. The regex is unanchored, so it matches names that
+/// contain `term` anywhere.
+///
+/// `*` and `?` become `.*` and `.`, and a `[...]` class is kept, with a leading `!` negating
+/// it. Everything else is matched literally.
+fn glob_to_regex(term: &str) -> String {
+    let chars: Vec<char> = term.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let class_end = chars[i + 1..].iter().position(|&c| c == ']');
+        match (chars[i], class_end) {
+            ('*', _) => regex.push_str(".*"),
+            ('?', _) => regex.push('.'),
+            ('[', Some(len)) => {
+                let mut class = &chars[i + 1..i + 1 + len];
+                regex.push('[');
+                if class.first() == Some(&'!') {
+                    regex.push('^');
+                    class = &class[1..];
+                }
+                for &c in class {
+                    if matches!(c, '\\' | '[' | '&' | '~') {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+                i += 1 + len;
+            }
+            (c, _) => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
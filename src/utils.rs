@@ -1,15 +1,28 @@
-use once_cell::sync::Lazy;
+use crate::error::StoreError;
+use crate::tree::{render, walk};
 use std::env;
-use std::fs;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
-pub static HOME: Lazy<String> =
-    Lazy::new(|| env::var("HOME").unwrap_or_else(|_| panic!("Error: $HOME is not set.")));
-
-// If PASSWORD_STORE_DIR is not set, default to "$HOME/.password-store"
-pub static PREFIX: Lazy<String> = Lazy::new(|| {
-    env::var("PASSWORD_STORE_DIR").unwrap_or_else(|_| format!("{}/.password-store", *HOME))
-});
+/// Returns the directory of the password store.
+///
+/// This is `PASSWORD_STORE_DIR` if it is set, and `$HOME/.password-store` otherwise.
+///
+/// # Returns
+///
+/// * `Ok(PathBuf)` with the store directory. It does not need to exist.
+/// * `Err(StoreError::InvalidInput)` if neither `PASSWORD_STORE_DIR` nor `HOME` is set.
+pub fn store_dir() -> Result<PathBuf, StoreError> {
+    if let Some(dir) = env::var_os("PASSWORD_STORE_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".password-store"))
+        .ok_or_else(|| StoreError::InvalidInput("$HOME is not set.".to_string()))
+}
 
 /// Checks for potentially dangerous path segments in the provided paths.
 ///
@@ -21,93 +34,183 @@ pub static PREFIX: Lazy<String> = Lazy::new(|| {
 /// - Contains `/../`
 /// - Is exactly equal to `..`
 ///
-/// If any of these conditions are met, the function will panic with an error message. This is a
-/// security measure to prevent passing "sneaky" paths into the application.
+/// If any of these conditions are met, an error is returned. This is a security measure to
+/// prevent passing "sneaky" paths into the application.
 ///
 /// # Arguments
 ///
 /// * `paths` - A vector of string slices, each representing a file or directory path.
 ///
-/// # Panics
+/// # Returns
 ///
-/// The function panics if any path is found that contains sneaky segments, with a message:
-/// "Error: You've attempted to pass a sneaky path to pass. Go home."
+/// * `Ok(())` if none of the paths contain sneaky segments.
+/// * `Err(StoreError::SneakyPath)` with the first offending path otherwise, which displays as
+///   "You've attempted to pass a sneaky path to pass. Go home."
 ///
 /// # Examples
 ///
 /// ```rust
-/// // This example will panic because "../unsafe/path" contains a dangerous pattern.
+/// # use password_store_rs::utils::check_sneaky_paths;
+/// // This example fails because "../unsafe/path" contains a dangerous pattern.
 /// let paths = vec!["safe/path", "../unsafe/path"];
-/// check_sneaky_paths(paths);
+/// assert!(check_sneaky_paths(paths).is_err());
 /// ```
-pub fn check_sneaky_paths(paths: Vec<&str>) {
+pub fn check_sneaky_paths(paths: Vec<&str>) -> Result<(), StoreError> {
     for path in paths {
         if path.ends_with("/..") || path.starts_with("../") || path.contains("/../") || path == ".."
         {
-            panic!("Error: You've attempted to pass a sneaky path to pass. Go home.");
+            return Err(StoreError::SneakyPath(path.to_string()));
         }
     }
+    Ok(())
 }
 
-/// Recursively prints the directory structure starting from the given path.
+/// Prints the files and folders under `path` as a tree, like `tree`.
 ///
-/// This function traverses the directory tree beginning at `path` and prints each entry
-/// (file or directory) along with a prefix to indicate its depth in the hierarchy. Hidden
-/// files (those starting with a dot) are skipped. Additionally, if a file has a `.gpg`
-/// extension, the extension is removed when printing the file name.
+/// Entries are listed by name without their `.gpg` or `.age` extension, sorted
+/// alphabetically, with box-drawing connectors. Hidden files and folders such as `.git` are
+/// skipped, as are folders that only contain hidden files, and symlinks are shown with their
+/// target (see [`crate::tree::walk`] and [`crate::tree::render`]).
 ///
 /// # Arguments
 ///
-/// * `path` - A reference to the [`std::path::Path`] that represents the root directory
-///            from which to start printing the structure.
-/// * `prefix` - A string used as a prefix for each printed entry to indicate the current
-///              depth in the directory tree. This should typically be an empty string when
-///              first called, and it will be extended recursively.
+/// * `path` - The folder whose contents are printed, usually the root of the store.
+/// * `color` - Whether to color folder names and symlinks, e.g. when standard output is a
+///   terminal.
+/// * `max_depth` - How many levels to print, or `None` for all of them.
 ///
 /// # Returns
 ///
-/// A [`std::io::Result<()>`] which is:
-/// - `Ok(())` if the directory structure was successfully printed.
-/// - An error if there was a problem reading one or more directory entries.
+/// * `Ok(())` once the tree has been printed.
+/// * `Err(StoreError::Io)` if a folder cannot be read.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use std::io::IsTerminal;
+/// # use password_store_rs::utils::print_dir_structure;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// # let store = password_store_rs::PasswordStore::open("/home/me/.password-store")?;
+/// // Print the top-level entries and folders of the store.
+/// print_dir_structure(store.root(), std::io::stdout().is_terminal(), Some(1))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn print_dir_structure(
+    path: &Path,
+    color: bool,
+    max_depth: Option<usize>,
+) -> Result<(), StoreError> {
+    print!("{}", render(&walk(path, None)?, color, max_depth));
+    Ok(())
+}
+
+/// Returns whether an executable named `name` can be found in `$PATH`.
+///
+/// # Arguments
+///
+/// * `name` - The bare name of the program, e.g. `"xclip"`.
 ///
 /// # Examples
 ///
 /// ```rust
-/// use std::path::Path;
-/// use your_crate::utils::print_dir_structure; // Adjust the import path as needed
+/// # use password_store_rs::utils::executable_in_path;
+/// if executable_in_path("wl-copy") {
+///     println!("Wayland clipboard available");
+/// }
+/// ```
+pub fn executable_in_path(name: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| {
+            env::split_paths(&paths).any(|dir| is_executable::is_executable(dir.join(name)))
+        })
+        .unwrap_or(false)
+}
+
+/// Asks the user a yes/no question on the terminal.
 ///
-/// // Print the structure of the current directory.
-/// if let Err(e) = print_dir_structure(Path::new("."), "".to_string()) {
-///     eprintln!("Error printing directory structure: {}", e);
+/// The prompt is printed followed by ` [y/N]: `, and any answer starting with `y`
+/// (case-insensitive) counts as yes. A failure to read the answer counts as no.
+///
+/// # Arguments
+///
+/// * `prompt` - The question to ask.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::utils::yesno;
+/// if yesno("An entry already exists for example.com. Overwrite it?") {
+///     // overwrite
 /// }
 /// ```
+pub fn yesno(prompt: &str) -> bool {
+    print!("{} [y/N]: ", prompt);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    answer.trim().to_lowercase().starts_with('y')
+}
+
+/// Upper bound on the number of worker threads [`parallel_map`] starts.
+pub const MAX_WORKERS: usize = 8;
+
+/// Calls `f` on every item of `items` on a bounded pool of worker threads.
+///
+/// At most [`MAX_WORKERS`] threads are started, and never more than the available parallelism
+/// or the number of items. Each worker takes the next unprocessed item until none are left.
 ///
-/// # Notes
+/// # Arguments
+///
+/// * `items` - The items to process.
+/// * `f` - The function to call on each item. It is called from several threads at once.
+///
+/// # Returns
+///
+/// The results of `f`, in the same order as `items`.
 ///
-/// This function directly prints the directory structure to standard output and uses recursion
-/// to traverse subdirectories. Make sure the provided `path` exists and is a directory.
-pub fn print_dir_structure(path: &Path, prefix: String) -> std::io::Result<()> {
-    if path.is_dir() {
-        for entry_result in fs::read_dir(path)? {
-            let entry = entry_result?;
-            let path = entry.path();
-            let filename = path.file_name().unwrap().to_str().unwrap();
+/// # Examples
+///
+/// ```rust,no_run
+/// # use password_store_rs::utils::parallel_map;
+/// # fn main() -> Result<(), password_store_rs::StoreError> {
+/// # let store = password_store_rs::PasswordStore::open("/home/me/.password-store")?;
+/// # let names = store.list()?;
+/// let contents = parallel_map(&names, |name| store.get(name));
+/// # Ok(())
+/// # }
+/// ```
+pub fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_WORKERS)
+        .min(items.len().max(1));
 
-            if path.is_dir() {
-                println!("{}─ {}", prefix, filename);
-                let new_prefix = format!("{}    ", prefix);
-                print_dir_structure(&path, new_prefix)?;
-            } else {
-                if filename.starts_with(".") {
-                    continue;
-                }
-                if filename.ends_with(".gpg") {
-                    println!("{}─ {}", prefix, &filename[..filename.len() - 4]);
-                } else {
-                    println!("{}─ {}", prefix, filename);
-                }
-            }
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                *results[index].lock().unwrap() = Some(f(item));
+            });
         }
-    }
-    Ok(())
+    });
+
+    // Every slot is filled once the scope ends, since a panicking worker panics the scope.
+    results
+        .into_iter()
+        .map(|slot| slot.into_inner().unwrap().expect("every item is processed"))
+        .collect()
 }